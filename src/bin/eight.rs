@@ -4,11 +4,81 @@ use std::{
 };
 
 use advent_of_code::load_file;
+#[cfg(test)]
+use advent_of_code::random::XorShift;
 
 fn main() {
     let data = load_file!("eight");
-    let mut grid: Grid = data.as_str().into();
+    let mut grid = Grid::try_from(data.as_str()).unwrap();
     grid.set_scores();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [mode, layer, format, path] = &args[..] {
+        if mode == "export" {
+            let layer = Layer::try_from(layer.as_str()).unwrap();
+            let image = match format.as_str() {
+                "ppm" => grid.to_ppm(layer, 8),
+                "svg" => grid.to_svg(layer, 8).into_bytes(),
+                _ => panic!("{format} is not a valid image format"),
+            };
+            std::fs::write(path, image).unwrap();
+            return;
+        }
+    }
+    let show = |cell: (usize, usize)| println!("{cell:?}: {}", grid[cell].score);
+    let outside = |cell: (usize, usize)| {
+        println!(
+            "{cell:?} is outside the {}x{} forest",
+            grid.width, grid.height
+        )
+    };
+    let numbers = |from: usize| -> Vec<usize> {
+        args[from..]
+            .iter()
+            .map(|arg| arg.parse().unwrap())
+            .collect()
+    };
+    match args.first().map(String::as_str) {
+        Some("above") => {
+            grid.scores_above(numbers(1)[0]).into_iter().for_each(show);
+            return;
+        }
+        Some("best") => {
+            let corners = numbers(1);
+            let (from, to) = ((corners[0], corners[1]), (corners[2], corners[3]));
+            match [from, to]
+                .into_iter()
+                .find(|cell| grid.get(*cell).is_none())
+            {
+                Some(cell) => outside(cell),
+                None => show(grid.best_within(from, to).unwrap()),
+            }
+            return;
+        }
+        Some("edge") => {
+            let edge = Edge::try_from(args[1].as_str()).unwrap();
+            match grid.best_visible_from(edge) {
+                Some(cell) => show(cell),
+                None => println!("No trees visible from {edge:?}"),
+            }
+            return;
+        }
+        Some("view") => {
+            let cell = numbers(1);
+            let cell = (cell[0], cell[1]);
+            let Some(tree) = grid.get(cell) else {
+                outside(cell);
+                return;
+            };
+            for edge in [Edge::North, Edge::East, Edge::South, Edge::West] {
+                println!("{edge:?}: {}", tree.distances[edge as usize]);
+            }
+            println!("Score: {}", tree.score);
+            return;
+        }
+        _ => (),
+    }
+    let visible = grid.cells.iter().filter(|tree| tree.visible).count();
+    println!("Visible: {visible}");
     let score = grid.cells.iter().max_by_key(|tree| tree.score).unwrap();
     println!("Max: {}: {}", score, score.score);
     // println!("Grid: {}\nScore: {}", grid, grid.calculate_score((2, 3)));
@@ -19,6 +89,31 @@ struct Tree {
     height: i8,
     visible: bool,
     score: usize,
+    /// Per edge, indexed by `Edge`.
+    visible_from: [bool; 4],
+    /// Trees seen towards each edge, indexed by `Edge`.
+    distances: [usize; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edge {
+    North,
+    East,
+    South,
+    West,
+}
+impl TryFrom<&str> for Edge {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "north" => Ok(Self::North),
+            "east" => Ok(Self::East),
+            "south" => Ok(Self::South),
+            "west" => Ok(Self::West),
+            e => Err(format!("{e} is not a valid edge")),
+        }
+    }
 }
 impl Display for Tree {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -29,13 +124,20 @@ impl Display for Tree {
         }
     }
 }
-impl From<char> for Tree {
-    fn from(value: char) -> Self {
-        Tree {
-            height: (value as i8) & 0x0F,
+impl TryFrom<char> for Tree {
+    type Error = String;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        let height = value
+            .to_digit(10)
+            .ok_or(format!("{value:?} is not a tree height"))?;
+        Ok(Tree {
+            height: height as i8,
             visible: false,
             score: 1,
-        }
+            visible_from: [false; 4],
+            distances: [0; 4],
+        })
     }
 }
 struct Grid {
@@ -69,34 +171,306 @@ impl IndexMut<(usize, usize)> for Grid {
     }
 }
 
-impl From<&str> for Grid {
-    fn from(value: &str) -> Self {
+impl TryFrom<&str> for Grid {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let value = value.strip_suffix('\n').unwrap_or(value);
+        if value.is_empty() {
+            return Err("forest has no trees".to_owned());
+        }
         let lines = value.split('\n').collect::<Vec<&str>>();
         let height = lines.len();
-        let width = lines[0].len();
+        let width = lines[0].chars().count();
         let mut grid = Grid {
             cells: Vec::with_capacity(height * width),
             height,
             width,
         };
-        for line in lines {
-            for ch in line.chars() {
-                grid.cells.push(ch.into())
+        for (y, line) in lines.iter().enumerate() {
+            let mut count = 0;
+            for (x, ch) in line.chars().enumerate() {
+                let tree = ch
+                    .try_into()
+                    .map_err(|e| format!("line {}, column {}: {e}", y + 1, x + 1))?;
+                grid.cells.push(tree);
+                count += 1;
+            }
+            if count != width {
+                return Err(format!(
+                    "line {} has {count} trees, expected {width}",
+                    y + 1
+                ));
             }
         }
-        grid
+        Ok(grid)
     }
 }
 
+#[test]
+fn parse_forest() {
+    let grid = Grid::try_from("123\n456\n").unwrap();
+    assert_eq!((grid.width, grid.height), (3, 2));
+    assert_eq!(grid[(2, 1)].height, 6);
+    assert_eq!(
+        Grid::try_from("123\n4a6").err(),
+        Some("line 2, column 2: 'a' is not a tree height".to_owned())
+    );
+    assert_eq!(
+        Grid::try_from("123\n45\n789").err(),
+        Some("line 2 has 2 trees, expected 3".to_owned())
+    );
+    assert_eq!(
+        Grid::try_from("123\n\n789").err(),
+        Some("line 2 has 0 trees, expected 3".to_owned())
+    );
+    assert!(Grid::try_from("12:\n456").is_err());
+    assert!(Grid::try_from("").is_err());
+    assert!(Grid::try_from("\n").is_err());
+}
+
 impl Grid {
+    /// Marks visibility and scenic score of every tree with one sweep per direction.
     fn set_scores(&mut self) {
+        for tree in self.cells.iter_mut() {
+            tree.visible = false;
+            tree.score = 1;
+        }
+        for y in 0..self.height {
+            let row: Vec<_> = (0..self.width).map(|x| (x, y)).collect();
+            self.sweep(&row, Edge::West);
+            self.sweep(&row.into_iter().rev().collect::<Vec<_>>(), Edge::East);
+        }
         for x in 0..self.width {
-            for y in 0..self.height {
-                let score = self.calculate_score((x, y));
-                self.index_mut((x, y)).score = score;
+            let column: Vec<_> = (0..self.height).map(|y| (x, y)).collect();
+            self.sweep(&column, Edge::North);
+            self.sweep(&column.into_iter().rev().collect::<Vec<_>>(), Edge::South);
+        }
+    }
+
+    /// Looks back along `line`, which starts at `edge`, from every tree. A stack keeps the trees
+    /// not yet hidden behind a taller one, so its top after popping shorter trees is the one
+    /// blocking the view.
+    fn sweep(&mut self, line: &[(usize, usize)], edge: Edge) {
+        let mut tallest = -1;
+        let mut blocking: Vec<usize> = Vec::new();
+        for (i, index) in line.iter().enumerate() {
+            let height = self[*index].height;
+            while blocking
+                .last()
+                .is_some_and(|b| self[line[*b]].height < height)
+            {
+                blocking.pop();
+            }
+            let distance = i - blocking.last().copied().unwrap_or(0);
+            blocking.push(i);
+            let tree = self.index_mut(*index);
+            tree.score *= distance;
+            tree.distances[edge as usize] = distance;
+            tree.visible_from[edge as usize] = height > tallest;
+            if height > tallest {
+                tree.visible = true;
+                tallest = height;
             }
         }
     }
+
+    fn coordinates(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)))
+    }
+
+    /// Highest scoring of `cells`, the first in reading order on ties.
+    fn best(&self, cells: impl Iterator<Item = (usize, usize)>) -> Option<(usize, usize)> {
+        cells.fold(None, |best, cell| match best {
+            Some(best) if self[best].score >= self[cell].score => Some(best),
+            _ => Some(cell),
+        })
+    }
+
+    fn scores_above(&self, k: usize) -> Vec<(usize, usize)> {
+        self.coordinates()
+            .filter(|cell| self[*cell].score > k)
+            .collect()
+    }
+
+    /// Tree at `cell`, if it is inside the grid.
+    fn get(&self, cell: (usize, usize)) -> Option<&Tree> {
+        (cell.0 < self.width && cell.1 < self.height).then(|| &self[cell])
+    }
+
+    /// Best cell between the corners `from` and `to`, both included and inside the grid.
+    fn best_within(&self, from: (usize, usize), to: (usize, usize)) -> Option<(usize, usize)> {
+        self.get(from)?;
+        self.get(to)?;
+        let xs = from.0.min(to.0)..=from.0.max(to.0);
+        let ys = from.1.min(to.1)..=from.1.max(to.1);
+        self.best(ys.flat_map(|y| xs.clone().map(move |x| (x, y))))
+    }
+
+    fn best_visible_from(&self, edge: Edge) -> Option<(usize, usize)> {
+        self.best(
+            self.coordinates()
+                .filter(|cell| self[*cell].visible_from[edge as usize]),
+        )
+    }
+}
+
+type Colour = (u8, u8, u8);
+
+/// What the cells of an exported image are coloured by.
+#[derive(Clone, Copy, Debug)]
+enum Layer {
+    Height,
+    Visibility,
+    Score,
+}
+impl TryFrom<&str> for Layer {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "height" => Ok(Self::Height),
+            "visibility" => Ok(Self::Visibility),
+            "score" => Ok(Self::Score),
+            e => Err(format!("{e} is not a valid layer")),
+        }
+    }
+}
+
+/// Dark purple through teal to yellow, `t` between 0 and 1.
+fn ramp(t: f64) -> Colour {
+    let stops = [
+        (68.0, 1.0, 84.0),
+        (33.0, 145.0, 140.0),
+        (253.0, 231.0, 37.0),
+    ];
+    let t = t.clamp(0.0, 1.0) * 2.0;
+    let (i, t) = if t < 1.0 { (0, t) } else { (1, t - 1.0) };
+    let (a, b) = (stops[i], stops[i + 1]);
+    let mix = |a: f64, b: f64| (a + (b - a) * t).round() as u8;
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
+
+const VISIBLE: Colour = (250, 190, 40);
+const HIDDEN: Colour = (40, 70, 45);
+/// Swatches in the legend of a gradient layer.
+const LEGEND_STEPS: usize = 10;
+const BACKGROUND: Colour = (255, 255, 255);
+
+/// Scores are spread on a square root scale, most trees score far below `max_score`.
+fn colour(tree: &Tree, layer: Layer, max_score: usize) -> Colour {
+    match layer {
+        Layer::Height => ramp(tree.height as f64 / 9.0),
+        Layer::Visibility if tree.visible => VISIBLE,
+        Layer::Visibility => HIDDEN,
+        Layer::Score => ramp((tree.score as f64 / max_score.max(1) as f64).sqrt()),
+    }
+}
+
+/// Labelled colours explaining `layer`, from low to high.
+fn legend(layer: Layer, max_score: usize) -> Vec<(String, Colour)> {
+    match layer {
+        Layer::Height => (0..=9)
+            .map(|h| (h.to_string(), ramp(h as f64 / 9.0)))
+            .collect(),
+        Layer::Visibility => vec![
+            ("hidden".to_owned(), HIDDEN),
+            ("visible".to_owned(), VISIBLE),
+        ],
+        Layer::Score => (0..LEGEND_STEPS)
+            .map(|i| {
+                let t = i as f64 / (LEGEND_STEPS - 1) as f64;
+                let score = (t * t * max_score as f64).round();
+                (score.to_string(), ramp(t))
+            })
+            .collect(),
+    }
+}
+
+impl Grid {
+    fn max_score(&self) -> usize {
+        self.cells.iter().map(|t| t.score).max().unwrap_or_default()
+    }
+
+    /// Binary PPM with `cell` pixels per tree, the legend is a row of unlabelled swatches below.
+    fn to_ppm(&self, layer: Layer, cell: usize) -> Vec<u8> {
+        let max_score = self.max_score();
+        let legend = legend(layer, max_score);
+        let width = (self.width * cell).max(legend.len());
+        let height = self.height * cell + 3 * cell;
+        let mut pixels = vec![BACKGROUND; width * height];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let colour = colour(&self[(x, y)], layer, max_score);
+                for py in y * cell..(y + 1) * cell {
+                    pixels[py * width + x * cell..py * width + (x + 1) * cell].fill(colour);
+                }
+            }
+        }
+        let swatch = width / legend.len();
+        for py in (self.height + 1) * cell..height {
+            for (i, (_, colour)) in legend.iter().enumerate() {
+                pixels[py * width + i * swatch..py * width + (i + 1) * swatch].fill(*colour);
+            }
+        }
+        let mut image = format!("P6\n{width} {height}\n255\n").into_bytes();
+        image.extend(pixels.iter().flat_map(|(r, g, b)| [*r, *g, *b]));
+        image
+    }
+
+    /// SVG with `cell` units per tree and a labelled legend below.
+    fn to_svg(&self, layer: Layer, cell: usize) -> String {
+        let max_score = self.max_score();
+        let legend = legend(layer, max_score);
+        let swatch = cell * 4;
+        let width = (self.width * cell).max(legend.len() * swatch);
+        let height = self.height * cell + cell + swatch * 2;
+        let hex = |(r, g, b): Colour| format!("#{r:02x}{g:02x}{b:02x}");
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">\n"
+        );
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let tree = &self[(x, y)];
+                svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{cell}\" height=\"{cell}\" fill=\"{}\">\
+                     <title>({x}, {y}) height {} score {}</title></rect>\n",
+                    x * cell,
+                    y * cell,
+                    hex(colour(tree, layer, max_score)),
+                    tree.height,
+                    tree.score
+                ));
+            }
+        }
+        let top = (self.height + 1) * cell;
+        for (i, (label, colour)) in legend.iter().enumerate() {
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{top}\" width=\"{swatch}\" height=\"{swatch}\" fill=\"{}\"/>\n\
+                 <text x=\"{}\" y=\"{}\" font-size=\"{cell}\">{label}</text>\n",
+                i * swatch,
+                hex(*colour),
+                i * swatch,
+                top + swatch + cell,
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Brute force walk from every tree, kept to check the sweeps against.
+#[cfg(test)]
+impl Grid {
+    fn calculate_visible(&self, index: (usize, usize)) -> bool {
+        let tree = self.index(index);
+        let (x, y) = index;
+        let lower = |other: (usize, usize)| self.index(other).height < tree.height;
+        (0..x).all(|x| lower((x, y)))
+            || (x + 1..self.width).all(|x| lower((x, y)))
+            || (0..y).all(|y| lower((x, y)))
+            || (y + 1..self.height).all(|y| lower((x, y)))
+    }
     fn calculate_score(&self, index: (usize, usize)) -> usize {
         self.calc_north(index)
             * self.calc_south(index)
@@ -148,3 +522,104 @@ impl Grid {
         steps
     }
 }
+
+#[test]
+fn sample_scores() {
+    let mut grid = Grid::try_from("30373\n25512\n65332\n33549\n35390").unwrap();
+    grid.set_scores();
+    assert_eq!(grid.cells.iter().filter(|t| t.visible).count(), 21);
+    assert_eq!(grid.cells.iter().map(|t| t.score).max(), Some(8));
+    assert_eq!(grid[(2, 3)].score, 8);
+}
+
+#[test]
+fn sweeps_match_brute_force() {
+    let mut random = XorShift::new(0x2545_F491_4F6C_DD1D);
+    let mut next = move |bound| random.below(bound);
+    for _ in 0..50 {
+        let (width, height) = (1 + next(30), 1 + next(30));
+        let rows: Vec<String> = (0..height)
+            .map(|_| {
+                (0..width)
+                    .map(|_| char::from(b'0' + next(10) as u8))
+                    .collect()
+            })
+            .collect();
+        let mut grid = Grid::try_from(rows.join("\n").as_str()).unwrap();
+        grid.set_scores();
+        for x in 0..grid.width {
+            for y in 0..grid.height {
+                assert_eq!(grid[(x, y)].score, grid.calculate_score((x, y)));
+                assert_eq!(grid[(x, y)].visible, grid.calculate_visible((x, y)));
+            }
+        }
+    }
+}
+
+#[test]
+fn image_export() {
+    let mut grid = Grid::try_from("30373\n25512\n65332\n33549\n35390").unwrap();
+    grid.set_scores();
+    let ppm = grid.to_ppm(Layer::Score, 2);
+    let header = b"P6\n10 16\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(ppm.len(), header.len() + 10 * 16 * 3);
+    // Top left tree has score 0, the darkest colour of the ramp.
+    assert_eq!(&ppm[header.len()..header.len() + 3], &[68, 1, 84]);
+    let svg = grid.to_svg(Layer::Visibility, 10);
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("<rect").count(), 25 + 2);
+    assert_eq!(svg.matches("fill=\"#2846").count(), 4 + 1);
+    assert!(svg.contains(">visible</text>"));
+    assert_eq!(
+        legend(Layer::Score, grid.max_score()).last().unwrap().0,
+        "8"
+    );
+    assert_eq!(legend(Layer::Height, grid.max_score()).len(), 10);
+}
+
+#[test]
+fn non_square_forest() {
+    let mut wide = Grid::try_from("3037325\n2551213\n6533261\n").unwrap();
+    wide.set_scores();
+    let transposed: Vec<String> = (0..wide.width)
+        .map(|x| {
+            (0..wide.height)
+                .map(|y| wide[(x, y)].height.to_string())
+                .collect()
+        })
+        .collect();
+    let mut tall = Grid::try_from(transposed.join("\n").as_str()).unwrap();
+    tall.set_scores();
+    for x in 0..wide.width {
+        for y in 0..wide.height {
+            assert_eq!(wide[(x, y)].height, tall[(y, x)].height);
+            assert_eq!(wide[(x, y)].score, tall[(y, x)].score);
+            assert_eq!(wide[(x, y)].visible, tall[(y, x)].visible);
+        }
+    }
+    assert_eq!(wide.cells.iter().filter(|t| t.visible).count(), 18);
+    assert_eq!(wide.to_ppm(Layer::Height, 1).len(), 12 + 10 * 6 * 3);
+}
+
+#[test]
+fn placement_queries() {
+    let mut grid = Grid::try_from("30373\n25512\n65332\n33549\n35390").unwrap();
+    grid.set_scores();
+    assert_eq!(grid.scores_above(4), vec![(1, 2), (2, 3)]);
+    assert_eq!(grid.scores_above(3), vec![(2, 1), (1, 2), (2, 3)]);
+    assert_eq!(grid.best_within((0, 0), (2, 2)), Some((1, 2)));
+    assert_eq!(grid.best_within((4, 4), (0, 3)), Some((2, 3)));
+    assert_eq!(grid.best_within((0, 0), (0, 0)), Some((0, 0)));
+    assert_eq!(grid.best_within((4, 4), (3, 3)), Some((3, 3)));
+    assert_eq!(grid.best_within((5, 0), (3, 3)), None);
+    assert_eq!(grid.best_within((0, 0), (3, 5)), None);
+    assert!(grid.get((4, 4)).is_some());
+    assert!(grid.get((5, 0)).is_none());
+    assert_eq!(grid.best_visible_from(Edge::South), Some((2, 3)));
+    assert_eq!(grid.best_visible_from(Edge::North), Some((2, 1)));
+    assert_eq!(grid[(2, 3)].distances, [2, 2, 1, 2]);
+    assert_eq!(grid[(2, 1)].distances, [1, 2, 2, 1]);
+    assert!(grid[(1, 1)].visible_from[Edge::North as usize]);
+    assert!(!grid[(1, 1)].visible_from[Edge::East as usize]);
+}
@@ -1,27 +1,56 @@
+#![feature(test)]
+extern crate test;
+
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
+    fmt::Display,
     ops::{Deref, DerefMut},
 };
 
 use advent_of_code::load_file;
+#[cfg(test)]
+use advent_of_code::random::XorShift;
 
 fn main() {
     let data = load_file!("five");
     let split_index = data.find("\nmove").unwrap();
     let (stacks, moves) = data.split_at(split_index);
-    let mut hold = Hold::try_from(stacks).unwrap();
-    let mut crane = Crane::try_from(moves).unwrap();
-    crane.move_crates(&mut hold).unwrap();
-    let top_crates = hold
-        .iter()
-        .map(|stack| stack.iter().last())
-        .collect::<Option<Vec<_>>>()
-        .unwrap();
-    let top_chars = top_crates.iter().map(|c| c.0);
-    println!("Ding ding, the answer is {}", String::from_iter(top_chars));
+    let hold = Hold::try_from(stacks).unwrap();
+    let crane = Crane::try_from(moves).unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match &args[..] {
+        [mode, model] if mode == "replay" => {
+            let model = CraneModel::try_from(model.as_str()).unwrap();
+            run_replay(Replay::new(hold, crane, model));
+        }
+        [mode, model, target, max_moves] if mode == "search" => {
+            let model = CraneModel::try_from(model.as_str()).unwrap();
+            match hold.search(target, model, max_moves.parse().unwrap()) {
+                Some(found) => found.iter().for_each(|m| println!("{m}")),
+                None => println!("No program of at most {max_moves} moves tops {target}"),
+            }
+        }
+        [mode, model] if mode == "simplify" => {
+            let model = CraneModel::try_from(model.as_str()).unwrap();
+            let simplified = crane.simplify(&hold, model).unwrap();
+            simplified.iter().for_each(|m| println!("{m}"));
+            println!(
+                "{} moves simplify to {} moves",
+                crane.len(),
+                simplified.len()
+            );
+        }
+        _ => {
+            for model in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+                let mut hold = hold.clone();
+                crane.clone().move_crates(&mut hold, model).unwrap();
+                println!("Ding ding, the {model:?} answer is {}", hold.top());
+            }
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct Crate(char);
 impl TryFrom<char> for Crate {
     type Error = String;
@@ -40,7 +69,7 @@ impl Deref for Crate {
         &self.0
     }
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct Stack(Vec<Crate>);
 impl Deref for Stack {
     type Target = Vec<Crate>;
@@ -54,7 +83,7 @@ impl DerefMut for Stack {
         &mut self.0
     }
 }
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct Hold(Vec<Stack>);
 impl Deref for Hold {
     type Target = Vec<Stack>;
@@ -85,7 +114,93 @@ impl TryFrom<&str> for Hold {
         Ok(Self(hold))
     }
 }
-#[derive(Clone, Copy, Debug)]
+impl Display for Hold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let height = self
+            .iter()
+            .map(|stack| stack.len())
+            .max()
+            .unwrap_or_default();
+        for level in (0..height).rev() {
+            for (i, stack) in self.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                match stack.get(level) {
+                    Some(c) => write!(f, "[{}]", c.0)?,
+                    None => write!(f, "   ")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        for i in 0..self.len() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:^3}", i + 1)?;
+        }
+        writeln!(f)
+    }
+}
+impl Hold {
+    /// Index of the 1-based stack `number`, if the hold has it.
+    fn stack_index(&self, number: usize) -> Result<usize, String> {
+        match number {
+            1.. if number <= self.len() => Ok(number - 1),
+            _ => Err(format!(
+                "stack {number} does not exist, the hold has {} stacks",
+                self.len()
+            )),
+        }
+    }
+
+    fn apply(&mut self, current_move: Move, model: CraneModel) -> Result<(), String> {
+        let from = self.stack_index(current_move.from)?;
+        let to = self.stack_index(current_move.to)?;
+        let from_len = self[from].len();
+        if current_move.amount > from_len {
+            return Err(format!(
+                "stack {} has height {from_len}, can not take {} crates",
+                current_move.from, current_move.amount
+            ));
+        }
+        // Crates put back on the stack they came from end up where they were, whether lifted
+        // one at a time or all at once.
+        if from == to {
+            return Ok(());
+        }
+        let picked = from_len - current_move.amount..;
+        let [source, target] = self.get_disjoint_mut([from, to]).unwrap();
+        match model {
+            CraneModel::CrateMover9000 => target.extend(source.drain(picked).rev()),
+            CraneModel::CrateMover9001 => target.extend(source.drain(picked)),
+        }
+        Ok(())
+    }
+
+    /// Copies of the stacks `current_move` takes from and puts on, with their indices.
+    fn touched(&self, current_move: Move) -> Result<Vec<(usize, Stack)>, String> {
+        let from = self.stack_index(current_move.from)?;
+        let to = self.stack_index(current_move.to)?;
+        let mut touched = vec![(from, self[from].clone())];
+        if to != from {
+            touched.push((to, self[to].clone()));
+        }
+        Ok(touched)
+    }
+
+    fn unchanged(&self, touched: &[(usize, Stack)]) -> bool {
+        touched.iter().all(|(i, stack)| self[*i] == *stack)
+    }
+
+    fn top(&self) -> String {
+        self.iter()
+            .filter_map(|stack| stack.last())
+            .map(|c| c.0)
+            .collect()
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct Move {
     from: usize,
     to: usize,
@@ -112,7 +227,33 @@ impl TryFrom<&str> for Move {
     }
 }
 
-#[derive(Debug)]
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.amount, self.from, self.to)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CraneModel {
+    /// Lifts one crate at a time, reversing the order of the moved crates.
+    CrateMover9000,
+    /// Lifts all crates at once, keeping their order.
+    CrateMover9001,
+}
+
+impl TryFrom<&str> for CraneModel {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "9000" => Ok(Self::CrateMover9000),
+            "9001" => Ok(Self::CrateMover9001),
+            e => Err(format!("{e} is not a known crane model")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 struct Crane(VecDeque<Move>);
 impl Deref for Crane {
     type Target = VecDeque<Move>;
@@ -139,16 +280,507 @@ impl TryFrom<&str> for Crane {
     }
 }
 impl Crane {
-    fn move_crates(&mut self, hold: &mut Hold) -> Result<(), String> {
+    fn move_crates(&mut self, hold: &mut Hold, model: CraneModel) -> Result<(), String> {
+        let mut number = 0;
         while let Some(current_move) = self.pop_front() {
-            let from_len = hold[current_move.from - 1].len();
-            let (first, second) =
-                hold[current_move.from - 1].split_at(from_len - current_move.amount);
-            let first = Stack(first.to_vec());
-            let mut picked_crates = second.to_vec();
-            hold[current_move.to - 1].append(&mut picked_crates);
-            hold[current_move.from - 1] = first;
+            number += 1;
+            hold.apply(current_move, model)
+                .map_err(|e| format!("move {number} ({current_move}): {e}"))?;
         }
         Ok(())
     }
+
+    /// Equivalent program for `hold`, with no-op moves dropped, back-and-forth pairs
+    /// cancelled and consecutive moves between the same stacks merged where that gives
+    /// the same arrangement. Only the stacks a move touches are saved, not whole holds.
+    fn simplify(&self, hold: &Hold, model: CraneModel) -> Result<Crane, String> {
+        let mut state = hold.clone();
+        let mut moves: Vec<Move> = Vec::new();
+        // Touched stacks before each kept move.
+        let mut before: Vec<Vec<(usize, Stack)>> = Vec::new();
+        for (number, &current_move) in (1..).zip(self.iter()) {
+            let touched = state
+                .touched(current_move)
+                .and_then(|touched| state.apply(current_move, model).map(|_| touched))
+                .map_err(|e| format!("move {number} ({current_move}): {e}"))?;
+            if state.unchanged(&touched) {
+                continue;
+            }
+            match (moves.last_mut(), before.last()) {
+                (Some(_), Some(previous))
+                    if state.unchanged(previous)
+                        && touched.iter().all(|(i, stack)| {
+                            previous.iter().any(|(j, _)| i == j) || state[*i] == *stack
+                        }) =>
+                {
+                    moves.pop();
+                    before.pop();
+                }
+                (Some(last), Some(previous))
+                    if last.from == current_move.from && last.to == current_move.to =>
+                {
+                    // Replay the merged move on just the touched stacks, numbered from 1.
+                    let mut merged_state = Hold(previous.iter().map(|(_, s)| s.clone()).collect());
+                    let merged = Move {
+                        amount: last.amount + current_move.amount,
+                        from: 1,
+                        to: previous.len(),
+                    };
+                    if merged_state.apply(merged, model).is_ok()
+                        && previous
+                            .iter()
+                            .zip(merged_state.iter())
+                            .all(|((i, _), stack)| state[*i] == *stack)
+                    {
+                        last.amount = merged.amount;
+                    } else {
+                        moves.push(current_move);
+                        before.push(touched);
+                    }
+                }
+                _ => {
+                    moves.push(current_move);
+                    before.push(touched);
+                }
+            }
+        }
+        Ok(Self(moves.into()))
+    }
+}
+
+impl Hold {
+    /// Shortest program, of at most `max_moves` moves, after which the top crates read `target`.
+    fn search(&self, target: &str, model: CraneModel, max_moves: usize) -> Option<Crane> {
+        let mut parents: HashMap<Hold, Option<(Hold, Move)>> = HashMap::new();
+        parents.insert(self.clone(), None);
+        let mut queue = VecDeque::from([(self.clone(), 0)]);
+        while let Some((hold, depth)) = queue.pop_front() {
+            if hold.top() == target {
+                let mut moves = VecDeque::new();
+                let mut current = &hold;
+                while let Some((parent, current_move)) = &parents[current] {
+                    moves.push_front(*current_move);
+                    current = parent;
+                }
+                return Some(Crane(moves));
+            }
+            if depth == max_moves {
+                continue;
+            }
+            for from in 1..=hold.len() {
+                // Moves onto the same stack never change the hold.
+                for to in (1..=hold.len()).filter(|to| *to != from) {
+                    for amount in 1..=hold[from - 1].len() {
+                        let current_move = Move { from, to, amount };
+                        let mut next = hold.clone();
+                        if next.apply(current_move, model).is_err() || parents.contains_key(&next) {
+                            continue;
+                        }
+                        parents.insert(next.clone(), Some((hold.clone(), current_move)));
+                        queue.push_back((next, depth + 1));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Move {
+    /// Move that puts the crates back, with the same crane model.
+    fn inverse(self) -> Move {
+        Move {
+            from: self.to,
+            to: self.from,
+            amount: self.amount,
+        }
+    }
+}
+
+/// Applies a crane program one move at a time, keeping enough to undo every step.
+struct Replay {
+    hold: Hold,
+    model: CraneModel,
+    moves: Vec<Move>,
+    undo: Vec<Move>,
+}
+
+impl Replay {
+    fn new(hold: Hold, crane: Crane, model: CraneModel) -> Self {
+        Self {
+            hold,
+            model,
+            moves: crane.0.into(),
+            undo: Vec::new(),
+        }
+    }
+
+    /// Number of moves applied so far.
+    fn position(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Applies the next move, returns `false` once the program is finished.
+    fn step(&mut self) -> Result<bool, String> {
+        let number = self.position() + 1;
+        let Some(&current_move) = self.moves.get(self.position()) else {
+            return Ok(false);
+        };
+        self.hold
+            .apply(current_move, self.model)
+            .map_err(|e| format!("move {number} ({current_move}): {e}"))?;
+        self.undo.push(current_move.inverse());
+        Ok(true)
+    }
+
+    /// Reverts the last applied move, returns `false` when at the start.
+    fn undo(&mut self) -> bool {
+        let Some(inverse) = self.undo.pop() else {
+            return false;
+        };
+        self.hold
+            .apply(inverse, self.model)
+            .expect("inverse of an applied move is always valid");
+        true
+    }
+
+    /// Steps forwards or backwards until exactly `step` moves are applied.
+    fn jump(&mut self, step: usize) -> Result<(), String> {
+        if step > self.moves.len() {
+            return Err(format!(
+                "step {step} is past the end, the program has {} moves",
+                self.moves.len()
+            ));
+        }
+        while self.position() > step {
+            self.undo();
+        }
+        while self.position() < step {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position().checked_sub(1).map(|i| self.moves[i]) {
+            Some(last) => writeln!(f, "step {}/{}: {last}", self.position(), self.moves.len())?,
+            None => writeln!(f, "step 0/{}", self.moves.len())?,
+        }
+        write!(f, "{}", self.hold)
+    }
+}
+
+/// Reads replay commands from stdin: empty or `n` steps, `u` undoes, `g N` jumps, `q` quits.
+fn run_replay(mut replay: Replay) {
+    println!("{replay}");
+    for line in std::io::stdin().lines() {
+        let line = line.unwrap();
+        let result = match line.trim().split_once(' ') {
+            Some(("g", step)) => step
+                .parse()
+                .or(Err(format!("{step} is not a valid step")))
+                .and_then(|step| replay.jump(step)),
+            None if matches!(line.trim(), "" | "n") => replay
+                .step()
+                .and_then(|stepped| stepped.then_some(()).ok_or("program finished".into())),
+            None if line.trim() == "u" => {
+                replay.undo();
+                Ok(())
+            }
+            None if line.trim() == "q" => break,
+            _ => Err(format!("{line} is not a valid command")),
+        };
+        match result {
+            Ok(()) => println!("{replay}"),
+            Err(e) => println!("{e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+const SAMPLE: &str = concat!(
+    "    [D]    \n",
+    "[N] [C]    \n",
+    "[Z] [M] [P]\n",
+    " 1   2   3 \n",
+    "\n",
+    "move 1 from 2 to 1\n",
+    "move 3 from 1 to 3\n",
+    "move 2 from 2 to 1\n",
+    "move 1 from 1 to 2",
+);
+
+#[cfg(test)]
+fn sample() -> (Hold, Crane) {
+    let (stacks, moves) = SAMPLE.split_at(SAMPLE.find("\nmove").unwrap());
+    (stacks.try_into().unwrap(), moves.try_into().unwrap())
+}
+
+#[test]
+fn crate_mover_9000() {
+    let (mut hold, mut crane) = sample();
+    crane
+        .move_crates(&mut hold, CraneModel::CrateMover9000)
+        .unwrap();
+    assert_eq!(hold.top(), "CMZ");
+}
+
+#[test]
+fn crate_mover_9001() {
+    let (mut hold, mut crane) = sample();
+    crane
+        .move_crates(&mut hold, CraneModel::CrateMover9001)
+        .unwrap();
+    assert_eq!(hold.top(), "MCD");
+}
+
+#[test]
+fn invalid_moves() {
+    let (hold, _) = sample();
+    let run = |moves: &str| {
+        let mut hold = hold.clone();
+        Crane::try_from(moves)
+            .unwrap()
+            .move_crates(&mut hold, CraneModel::CrateMover9001)
+    };
+    assert_eq!(
+        run("move 1 from 0 to 1"),
+        Err("move 1 (move 1 from 0 to 1): stack 0 does not exist, the hold has 3 stacks".into())
+    );
+    assert_eq!(
+        run("move 1 from 1 to 2\nmove 1 from 2 to 4"),
+        Err("move 2 (move 1 from 2 to 4): stack 4 does not exist, the hold has 3 stacks".into())
+    );
+    assert_eq!(
+        run("move 1 from 3 to 1\nmove 1 from 3 to 2"),
+        Err("move 2 (move 1 from 3 to 2): stack 3 has height 0, can not take 1 crates".into())
+    );
+    assert_eq!(run("move 3 from 2 to 2"), Ok(()));
+}
+
+#[test]
+fn hold_drawing_round_trip() {
+    let (stacks, _) = SAMPLE.split_at(SAMPLE.find("\nmove").unwrap());
+    let (mut hold, mut crane) = sample();
+    assert_eq!(hold.to_string(), stacks);
+    crane
+        .move_crates(&mut hold, CraneModel::CrateMover9000)
+        .unwrap();
+    let drawing = hold.to_string();
+    assert_eq!(
+        drawing,
+        concat!(
+            "        [Z]\n",
+            "        [N]\n",
+            "        [D]\n",
+            "[C] [M] [P]\n",
+            " 1   2   3 \n",
+        )
+    );
+    assert_eq!(Hold::try_from(drawing.as_str()), Ok(hold));
+    let data = load_file!("five");
+    let (stacks, _) = data.split_at(data.find("\nmove").unwrap());
+    assert_eq!(Hold::try_from(stacks).unwrap().to_string(), stacks);
+    let empty = Hold(vec![Stack(Vec::new()); 2]);
+    assert_eq!(Hold::try_from(empty.to_string().as_str()), Ok(empty));
+}
+
+#[test]
+fn replay_undo() {
+    for model in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+        let (hold, crane) = sample();
+        let mut replay = Replay::new(hold.clone(), crane.clone(), model);
+        let mut snapshots = vec![hold.to_string()];
+        while replay.step().unwrap() {
+            snapshots.push(replay.hold.to_string());
+        }
+        assert_eq!(replay.position(), 4);
+        let mut finished = hold.clone();
+        crane.clone().move_crates(&mut finished, model).unwrap();
+        assert_eq!(replay.hold, finished);
+        for step in [2, 0, 3, 1, 4] {
+            replay.jump(step).unwrap();
+            assert_eq!(replay.hold.to_string(), snapshots[step]);
+        }
+        while replay.undo() {}
+        assert_eq!(replay.hold, hold);
+        assert!(replay.jump(5).is_err());
+    }
+}
+
+#[test]
+fn simplify_program() {
+    let (hold, _) = sample();
+    for model in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+        let crane = Crane::try_from(concat!(
+            "move 1 from 2 to 1\n",
+            "move 0 from 3 to 1\n",
+            "move 1 from 3 to 2\n",
+            "move 1 from 2 to 3\n",
+            "move 1 from 1 to 3\n",
+            "move 1 from 1 to 3\n",
+            "move 2 from 2 to 2",
+        ))
+        .unwrap();
+        let simplified = crane.simplify(&hold, model).unwrap();
+        let mut expected = hold.clone();
+        crane.clone().move_crates(&mut expected, model).unwrap();
+        let mut actual = hold.clone();
+        simplified.clone().move_crates(&mut actual, model).unwrap();
+        assert_eq!(actual, expected);
+        assert!(simplified.iter().all(|m| m.from != m.to));
+        let moves: Vec<String> = simplified.iter().map(|m| m.to_string()).collect();
+        match model {
+            CraneModel::CrateMover9000 => {
+                assert_eq!(moves, ["move 1 from 2 to 1", "move 2 from 1 to 3"])
+            }
+            CraneModel::CrateMover9001 => assert_eq!(
+                moves,
+                [
+                    "move 1 from 2 to 1",
+                    "move 1 from 1 to 3",
+                    "move 1 from 1 to 3"
+                ]
+            ),
+        }
+    }
+    let (hold, crane) = sample();
+    assert!(Crane::try_from("move 5 from 1 to 2")
+        .unwrap()
+        .simplify(&hold, CraneModel::CrateMover9000)
+        .is_err());
+    assert_eq!(
+        crane
+            .simplify(&hold, CraneModel::CrateMover9001)
+            .unwrap()
+            .len(),
+        4
+    );
+    let (hold, crane) = generate_program(3, 2, 2_000);
+    for model in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+        let mut simplified = crane.simplify(&hold, model).unwrap();
+        assert!(simplified.len() < crane.len());
+        let mut expected = hold.clone();
+        crane.clone().move_crates(&mut expected, model).unwrap();
+        let mut actual = hold.clone();
+        simplified.move_crates(&mut actual, model).unwrap();
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn search_top_crates() {
+    let (hold, _) = sample();
+    let found = hold.search("ZDP", CraneModel::CrateMover9001, 3).unwrap();
+    assert_eq!(found.len(), 2);
+    let mut after = hold.clone();
+    found
+        .clone()
+        .move_crates(&mut after, CraneModel::CrateMover9001)
+        .unwrap();
+    assert_eq!(after.top(), "ZDP");
+    assert!(hold.search("XYZ", CraneModel::CrateMover9001, 2).is_none());
+    let mut found = hold.search("ZDP", CraneModel::CrateMover9000, 3).unwrap();
+    assert!(found.iter().all(|m| m.from != m.to));
+    let mut after = hold.clone();
+    found
+        .move_crates(&mut after, CraneModel::CrateMover9000)
+        .unwrap();
+    assert_eq!(after.top(), "ZDP");
+    assert_eq!(
+        hold.search("NDP", CraneModel::CrateMover9000, 0)
+            .unwrap()
+            .len(),
+        0
+    );
+}
+
+/// Previous copying implementation of `Hold::apply`, kept as a reference.
+#[cfg(test)]
+fn apply_copying(hold: &mut Hold, current_move: Move, model: CraneModel) {
+    if current_move.from == current_move.to {
+        return;
+    }
+    let from_len = hold[current_move.from - 1].len();
+    let (first, second) = hold[current_move.from - 1].split_at(from_len - current_move.amount);
+    let first = Stack(first.to_vec());
+    let mut picked_crates = second.to_vec();
+    if let CraneModel::CrateMover9000 = model {
+        picked_crates.reverse();
+    }
+    hold[current_move.from - 1] = first;
+    hold[current_move.to - 1].append(&mut picked_crates);
+}
+
+/// Pseudo-random hold of `stacks` stacks with `depth` crates each, and a valid program for it.
+#[cfg(test)]
+fn generate_program(stacks: usize, depth: usize, moves: usize) -> (Hold, Crane) {
+    let mut random = XorShift::new(0x9E37_79B9_7F4A_7C15);
+    let mut next = move |bound| random.below(bound);
+    let hold = Hold(
+        (0..stacks)
+            .map(|_| {
+                let crates = (0..depth).map(|_| Crate((b'A' + next(26) as u8) as char));
+                Stack(crates.collect())
+            })
+            .collect(),
+    );
+    let mut heights = vec![depth; stacks];
+    let crane = (0..moves)
+        .map(|_| {
+            let from = loop {
+                let from = next(stacks);
+                if heights[from] > 0 {
+                    break from;
+                }
+            };
+            let to = next(stacks);
+            let amount = 1 + next(heights[from].min(64));
+            heights[from] -= amount;
+            heights[to] += amount;
+            Move {
+                from: from + 1,
+                to: to + 1,
+                amount,
+            }
+        })
+        .collect();
+    (hold, Crane(crane))
+}
+
+#[test]
+fn in_place_matches_copying() {
+    for model in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+        let (hold, crane) = generate_program(9, 200, 20_000);
+        let mut expected = hold.clone();
+        for current_move in crane.iter() {
+            apply_copying(&mut expected, *current_move, model);
+        }
+        let mut actual = hold;
+        crane.clone().move_crates(&mut actual, model).unwrap();
+        assert_eq!(actual, expected);
+        let (hold, _) = sample();
+        let self_move = Move::try_from("move 2 from 2 to 2").unwrap();
+        let mut copied = hold.clone();
+        apply_copying(&mut copied, self_move, model);
+        let mut in_place = hold.clone();
+        in_place.apply(self_move, model).unwrap();
+        assert_eq!(copied, hold);
+        assert_eq!(in_place, hold);
+    }
+}
+
+#[bench]
+fn bench_million_moves(b: &mut test::Bencher) {
+    let (hold, crane) = generate_program(9, 100_000, 1_000_000);
+    b.iter(|| {
+        let mut hold = hold.clone();
+        crane
+            .clone()
+            .move_crates(&mut hold, CraneModel::CrateMover9000)
+            .unwrap();
+        hold.top()
+    });
 }
@@ -1,46 +1,46 @@
-use advent_of_code::load_file;
+use std::fmt::Display;
+
+use advent_of_code::{
+    interval::{Interval, IntervalSet},
+    load_file,
+};
 
 fn main() {
     let data = load_file!("four");
-    let list: Result<List, String> = data.as_str().try_into();
-    let count = list
-        .unwrap()
-        .0
-        .iter()
-        .filter(|f| {
-            let overlap = f.overlap();
-            if overlap.1 >= overlap.0 {
-                return true;
-            }
-            false
-        })
-        .count();
-    println!("Ding ding, the answer is {count}!");
+    let list: List = data.as_str().try_into().unwrap();
+    let contained = list.0.iter().filter(|pair| pair.fully_contained()).count();
+    println!("Ding ding, the containment answer is {contained}!");
+    let overlapping = list.0.iter().filter(|pair| pair.overlapping()).count();
+    println!("Ding ding, the overlap answer is {overlapping}!");
+    print!("{}", list.coverage_report());
 }
 
-#[derive(Clone, Copy, PartialEq)]
-struct Section(u32, u32);
-
-impl TryFrom<&str> for Section {
-    type Error = String;
+type Section = Interval;
 
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let (start, end) = value
-            .split_once('-')
-            .ok_or(format!("{value} is not valid Section"))?;
-        Ok(Self(
-            start.parse().or(Err(format!("{start} is not u32")))?,
-            end.parse().or(Err(format!("{end} is not u32")))?,
-        ))
-    }
+#[test]
+fn section_predicates() {
+    let section = |s, e| Section::new(s, e).unwrap();
+    let single = Section::point(4);
+    assert!(single.contains(single));
+    assert!(single.overlaps(single));
+    assert!(section(2, 6).contains(single));
+    assert!(!single.contains(section(2, 6)));
+    assert!(section(4, 6).overlaps(section(6, 8)));
+    assert!(!section(4, 6).overlaps(section(7, 8)));
+    assert!(!section(7, 8).overlaps(section(4, 6)));
+    assert!(section(3, 7).contains(section(3, 7)));
 }
 
 #[derive(Clone, Copy)]
 struct Pair(Section, Section);
 
 impl Pair {
-    fn overlap(self) -> Section {
-        Section((self.0).0.max((self.1).0), (self.0).1.min((self.1).1))
+    fn fully_contained(self) -> bool {
+        self.0.contains(self.1) || self.1.contains(self.0)
+    }
+
+    fn overlapping(self) -> bool {
+        self.0.overlaps(self.1)
     }
 }
 
@@ -68,3 +68,135 @@ impl TryFrom<&str> for List {
         Ok(Self(list))
     }
 }
+
+impl List {
+    /// Every elf's section in file order, two per pair.
+    fn sections(&self) -> Vec<Section> {
+        self.0.iter().flat_map(|pair| [pair.0, pair.1]).collect()
+    }
+
+    fn coverage_report(&self) -> Report {
+        let sections = self.sections();
+        let coverage = Coverage::sweep(&sections);
+        let doubled = coverage.at_least(2);
+        Report {
+            deepest: coverage.deepest(),
+            uncovered: coverage.uncovered(),
+            redundant: (1..)
+                .zip(&sections)
+                .filter(|(_, section)| doubled.covers(**section))
+                .map(|(elf, _)| Interval::point(elf))
+                .collect(),
+        }
+    }
+}
+
+/// Number of elves assigned to each run of sections, from the first to the last assigned one.
+struct Coverage(Vec<(Interval, usize)>);
+
+impl Coverage {
+    fn sweep(sections: &[Section]) -> Self {
+        let mut events: Vec<(i64, isize)> = sections
+            .iter()
+            .flat_map(|s| [(s.start(), 1), (s.end() + 1, -1)])
+            .collect();
+        events.sort_unstable();
+        let mut runs = Vec::new();
+        let mut depth = 0;
+        for (i, (at, change)) in events.iter().enumerate() {
+            depth += change;
+            if let Some(run) = events
+                .get(i + 1)
+                .and_then(|(next, _)| Interval::new(*at, next - 1))
+            {
+                runs.push((run, depth as usize));
+            }
+        }
+        Self(runs)
+    }
+
+    fn at_least(&self, depth: usize) -> IntervalSet {
+        self.0
+            .iter()
+            .filter(|(_, d)| *d >= depth)
+            .map(|(run, _)| *run)
+            .collect()
+    }
+
+    fn deepest(&self) -> (usize, IntervalSet) {
+        let max = self.0.iter().map(|(_, d)| *d).max().unwrap_or_default();
+        let runs = self
+            .0
+            .iter()
+            .filter(|(_, d)| *d == max)
+            .map(|(run, _)| *run)
+            .collect();
+        (max, runs)
+    }
+
+    fn uncovered(&self) -> IntervalSet {
+        self.0
+            .iter()
+            .filter(|(_, d)| *d == 0)
+            .map(|(run, _)| *run)
+            .collect()
+    }
+}
+
+/// Elves are numbered from 1 in file order, the left elf of each pair first.
+struct Report {
+    deepest: (usize, IntervalSet),
+    uncovered: IntervalSet,
+    redundant: IntervalSet,
+}
+
+/// Joins runs as `a-b`, or just `a` for a single value.
+fn compact(set: &IntervalSet) -> String {
+    if set.is_empty() {
+        return "none".to_owned();
+    }
+    set.iter()
+        .map(|run| match run.length() {
+            Some(1) => run.start().to_string(),
+            _ => run.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (depth, runs) = &self.deepest;
+        writeln!(f, "most covered: {depth} elves on {}", compact(runs))?;
+        writeln!(f, "uncovered: {}", compact(&self.uncovered))?;
+        writeln!(
+            f,
+            "redundant elves ({}): {}",
+            self.redundant
+                .coverage()
+                .expect("elf numbers never span the whole i64 range"),
+            compact(&self.redundant)
+        )
+    }
+}
+
+#[test]
+fn coverage_report() {
+    let list = List::try_from("2-4,6-8\n2-3,4-5\n5-7,7-9\n2-8,3-7\n6-6,4-6\n2-6,4-8\n12-13,13-13")
+        .unwrap();
+    let report = list.coverage_report();
+    assert_eq!(report.deepest.0, 8);
+    assert_eq!(
+        report.to_string(),
+        "most covered: 8 elves on 6\n\
+         uncovered: 10-11\n\
+         redundant elves (12): 1-5,7-12,14\n"
+    );
+}
+
+#[test]
+fn sample_counts() {
+    let list = List::try_from("2-4,6-8\n2-3,4-5\n5-7,7-9\n2-8,3-7\n6-6,4-6\n2-6,4-8").unwrap();
+    assert_eq!(list.0.iter().filter(|p| p.fully_contained()).count(), 2);
+    assert_eq!(list.0.iter().filter(|p| p.overlapping()).count(), 4);
+}
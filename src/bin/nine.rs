@@ -5,12 +5,23 @@ use advent_of_code::load_file;
 fn main() {
     let data = load_file!("nine");
     let move_list: Vec<Move> = data.split('\n').map(Move::from).collect();
-    let mut board = Board::default();
+    let knots = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().unwrap())
+        .unwrap_or(10);
+    let mut board = Board::new(knots).unwrap();
     // println!("{}", &board);
     for mv in move_list {
         board.exec_move(mv);
     }
-    println!("Total unique positions: {}", board.move_set.len());
+    for (knot, visited) in board.visited.iter().enumerate().skip(1) {
+        println!("Knot {knot} visited {} unique positions", visited.len());
+    }
+    for (part, knot) in [("two knot", 1), ("ten knot", 9)] {
+        if let Some(visited) = board.visited.get(knot) {
+            println!("Ding ding, the {part} answer is {}", visited.len());
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -116,20 +127,34 @@ impl Position {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 struct Board {
-    rope: [Position; 10],
-    move_set: BTreeSet<Position>,
+    rope: Vec<Position>,
+    /// Positions each knot has been at, indexed like `rope`.
+    visited: Vec<BTreeSet<Position>>,
 }
 
 impl Board {
+    fn new(knots: usize) -> Result<Self, String> {
+        if knots == 0 {
+            return Err("rope needs at least 1 knot".to_owned());
+        }
+        Ok(Self {
+            rope: vec![Position::default(); knots],
+            visited: vec![BTreeSet::from([Position::default()]); knots],
+        })
+    }
+
     fn exec_move(&mut self, mv: Move) {
         let (head, mv) = self.rope[0].exec_move(mv);
         self.rope[0] = head;
-        for i in 1..10 {
-            self.rope[i].update_pos(self.rope[i - 1]);
+        for i in 1..self.rope.len() {
+            let ahead = self.rope[i - 1];
+            self.rope[i].update_pos(ahead);
+        }
+        for (visited, knot) in self.visited.iter_mut().zip(&self.rope) {
+            visited.insert(*knot);
         }
-        self.move_set.insert(self.rope[9]);
         // println!("{}", &self);
         if let Some(mv) = mv {
             self.exec_move(mv);
@@ -154,3 +179,26 @@ impl Display for Board {
         writeln!(f)
     }
 }
+
+#[cfg(test)]
+fn simulate(data: &str, knots: usize) -> Board {
+    let mut board = Board::new(knots).unwrap();
+    for mv in data.split('\n').map(Move::from) {
+        board.exec_move(mv);
+    }
+    board
+}
+
+#[test]
+fn sample_ropes() {
+    let board = simulate("R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2", 10);
+    assert_eq!(board.visited[1].len(), 13);
+    assert_eq!(board.visited[9].len(), 1);
+    // A shorter rope moves exactly like the front of a longer one.
+    let short = simulate("R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2", 2);
+    assert_eq!(short.visited[..], board.visited[..2]);
+    let board = simulate("R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20", 10);
+    assert_eq!(board.visited[9].len(), 36);
+    assert_eq!(simulate("R 3", 1).visited[0].len(), 4);
+    assert!(Board::new(0).is_err());
+}
@@ -1,8 +1,53 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
 
-use advent_of_code::load_file;
+use advent_of_code::{load_file, random::XorShift};
 
 fn main() {
+    let mut disk = Disk::default();
+    let mut show_stats = false;
+    let mut render = None;
+    let mut sort = SortBy::Name;
+    let mut depth = None;
+    let mut query = None;
+    let mut generate = None;
+    let mut nodes = 200;
+    let mut options = TranscriptOptions {
+        order: Traversal::DepthFirst,
+        repeat: false,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap();
+        match arg.as_str() {
+            "--capacity" => disk.capacity = value().parse().unwrap(),
+            "--required" => disk.required = value().parse().unwrap(),
+            "--threshold" => disk.threshold = value().parse().unwrap(),
+            "--stats" => show_stats = true,
+            "--sort" => sort = value().as_str().try_into().unwrap(),
+            "--depth" => depth = Some(value().parse().unwrap()),
+            "query" => query = Some(value()),
+            "generate" => generate = Some((value(), value())),
+            "--nodes" => nodes = value().parse().unwrap(),
+            "--order" => options.order = value().as_str().try_into().unwrap(),
+            "--repeat" => options.repeat = true,
+            "tree" | "du" | "verify" => render = Some(arg),
+            _ => panic!("unknown argument {arg}"),
+        }
+    }
+    if let Some((source, from)) = generate {
+        let generated = match source.as_str() {
+            "random" => FileSystem::random(from.parse().unwrap(), nodes),
+            "disk" => FileSystem::from_disk(Path::new(&from)).unwrap(),
+            _ => panic!("unknown transcript source {source}"),
+        };
+        print!("{}", generated.transcript(options));
+        return;
+    }
     let data = load_file!("seven");
     let commands = data
         .split("$ ")
@@ -10,10 +55,77 @@ fn main() {
         .map(Command::try_from)
         .collect::<Result<Vec<_>, String>>()
         .unwrap();
-    let sizes = directory_sizes(commands);
-    let clear_size = 30000000 - (70000000 - sizes["//"]);
-    let total_size = sizes.values().filter(|s| **s > clear_size).min().unwrap();
-    println!("Ding ding, the answer is {total_size}");
+    let fs = FileSystem::try_from(commands).unwrap();
+    let sizes = fs.sizes();
+    if let Some(query) = query {
+        let query = Query::try_from(query.as_str()).unwrap();
+        print!("{}", fs.render(&sizes, &fs.query(&sizes, &query).unwrap()));
+        return;
+    }
+    match render.as_deref() {
+        Some("tree") => {
+            print!("{}", fs.tree(&sizes, sort, depth));
+            return;
+        }
+        Some("du") => {
+            print!("{}", fs.du(&sizes, depth));
+            return;
+        }
+        Some("verify") => {
+            let dir = TempDir::new("verify").unwrap();
+            let discrepancies = fs.cross_check(&sizes, &dir.0).unwrap();
+            if discrepancies.is_empty() {
+                println!(
+                    "All {} directory sizes match the disk",
+                    fs.directories().count()
+                );
+            }
+            for discrepancy in discrepancies {
+                println!("{discrepancy}");
+            }
+            return;
+        }
+        _ => (),
+    }
+    let small_total = fs.small_directories_total(&sizes, disk.threshold);
+    println!("Ding ding, the small directories answer is {small_total}");
+    match fs.smallest_to_delete(&sizes, disk) {
+        Some(size) => println!("Ding ding, the delete answer is {size}"),
+        None => println!("No single directory frees enough space"),
+    }
+    if show_stats {
+        print!("{}", fs.statistics);
+    }
+}
+
+/// Disk geometry for the puzzle answers, defaults to the puzzle's numbers.
+#[derive(Clone, Copy, Debug)]
+struct Disk {
+    capacity: usize,
+    required: usize,
+    /// Largest size counted as a small directory.
+    threshold: usize,
+}
+impl Default for Disk {
+    fn default() -> Self {
+        Self {
+            capacity: 70000000,
+            required: 30000000,
+            threshold: 100000,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Statistics {
+    cd_count: usize,
+    ls_count: usize,
+}
+impl Display for Statistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "cd commands: {}", self.cd_count)?;
+        writeln!(f, "ls commands: {}", self.ls_count)
+    }
 }
 
 #[derive(Debug)]
@@ -43,6 +155,14 @@ impl TryFrom<&str> for Entry {
         Ok(entry)
     }
 }
+impl Entry {
+    fn name(&self) -> &str {
+        match self {
+            Entry::File(file) => &file.name,
+            Entry::Directory(dir) => &dir.name,
+        }
+    }
+}
 #[derive(Debug)]
 struct File {
     name: String,
@@ -62,7 +182,7 @@ impl TryFrom<&str> for Command {
     type Error = String;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let (command, other) = value.split_once('\n').ok_or(format!("{value} is empty"))?;
+        let (command, other) = value.split_once('\n').unwrap_or((value, ""));
         if let Some(("cd", second)) = command.split_once(' ') {
             Ok(Self::ChangeDir(second.into()))
         } else if command == "ls" {
@@ -88,63 +208,1119 @@ impl TryFrom<&str> for List {
 }
 #[derive(Debug)]
 enum ChangeDir {
+    Root,
     Up,
+    /// Relative or absolute path, possibly with several components.
     Down(String),
 }
 impl From<&str> for ChangeDir {
     fn from(value: &str) -> Self {
         match value {
+            "/" => Self::Root,
             ".." => Self::Up,
             dir => Self::Down(dir.to_owned()),
         }
     }
 }
 
-fn directory_sizes(commands: Vec<Command>) -> HashMap<String, usize> {
-    let mut stack: Vec<String> = Vec::new();
-    let mut sizes: HashMap<String, usize> = HashMap::new();
-    let mut cd_count = 0;
-    let mut ls_count = 0;
-    for command in commands {
-        match command {
-            Command::List(ls) => {
-                ls_count += 1;
-                for entry in ls.0 {
-                    match entry {
-                        Entry::File(file) => {
-                            for back in stack.iter() {
-                                sizes
-                                    .entry(back.to_string())
-                                    .and_modify(|v| *v += file.size)
-                                    .or_insert(file.size);
-                            }
+type NodeId = usize;
+
+const ROOT: NodeId = 0;
+
+#[derive(Debug)]
+struct Node {
+    entry: Entry,
+    parent: Option<NodeId>,
+    children: BTreeMap<String, NodeId>,
+}
+
+/// Directory tree reconstructed from a shell transcript, nodes refer to each other by index.
+#[derive(Debug)]
+struct FileSystem {
+    nodes: Vec<Node>,
+    statistics: Statistics,
+}
+
+impl FileSystem {
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                entry: Entry::Directory(Directory {
+                    name: "/".to_owned(),
+                }),
+                parent: None,
+                children: BTreeMap::new(),
+            }],
+            statistics: Statistics::default(),
+        }
+    }
+
+    fn is_directory(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].entry, Entry::Directory(_))
+    }
+
+    fn directories(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len()).filter(|id| self.is_directory(*id))
+    }
+
+    /// Adds `entry` under `parent`, or returns the node already listed with that name.
+    fn insert(&mut self, parent: NodeId, entry: Entry) -> Result<NodeId, String> {
+        let name = entry.name();
+        if let Some(&id) = self.nodes[parent].children.get(name) {
+            return match (&self.nodes[id].entry, &entry) {
+                (Entry::File(old), Entry::File(new)) if old.size != new.size => Err(format!(
+                    "{name} listed with sizes {} and {}",
+                    old.size, new.size
+                )),
+                (Entry::File(_), Entry::Directory(_)) | (Entry::Directory(_), Entry::File(_)) => {
+                    Err(format!("{name} listed as both file and directory"))
+                }
+                _ => Ok(id),
+            };
+        }
+        let id = self.nodes.len();
+        self.nodes[parent].children.insert(name.to_owned(), id);
+        self.nodes.push(Node {
+            entry,
+            parent: Some(parent),
+            children: BTreeMap::new(),
+        });
+        Ok(id)
+    }
+
+    fn change_dir(&mut self, cwd: NodeId, cd: ChangeDir) -> Result<NodeId, String> {
+        match cd {
+            ChangeDir::Root => Ok(ROOT),
+            ChangeDir::Up => Ok(self.nodes[cwd].parent.unwrap_or(ROOT)),
+            ChangeDir::Down(path) => {
+                let mut cwd = if path.starts_with('/') { ROOT } else { cwd };
+                for part in path.split('/').filter(|p| !p.is_empty()) {
+                    cwd = match part {
+                        "." => cwd,
+                        ".." => self.nodes[cwd].parent.unwrap_or(ROOT),
+                        name => self.insert(
+                            cwd,
+                            Entry::Directory(Directory {
+                                name: name.to_owned(),
+                            }),
+                        )?,
+                    };
+                }
+                Ok(cwd)
+            }
+        }
+    }
+
+    /// Total size of every node, directories include everything below them.
+    fn sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![0; self.nodes.len()];
+        let mut stack = vec![(ROOT, false)];
+        while let Some((id, visited)) = stack.pop() {
+            let node = &self.nodes[id];
+            if visited {
+                sizes[id] = match &node.entry {
+                    Entry::File(file) => file.size,
+                    Entry::Directory(_) => node.children.values().map(|c| sizes[*c]).sum(),
+                };
+            } else {
+                stack.push((id, true));
+                stack.extend(node.children.values().map(|c| (*c, false)));
+            }
+        }
+        sizes
+    }
+
+    /// Children of `id` in the order requested, largest first when sorting by size.
+    fn sorted_children(&self, id: NodeId, sizes: &[usize], sort: SortBy) -> Vec<NodeId> {
+        let mut children: Vec<NodeId> = self.nodes[id].children.values().copied().collect();
+        if let SortBy::Size = sort {
+            children.sort_by_key(|c| std::cmp::Reverse(sizes[*c]));
+        }
+        children
+    }
+
+    fn path(&self, id: NodeId) -> String {
+        match self.nodes[id].parent {
+            None => "/".to_owned(),
+            Some(ROOT) => format!("/{}", self.nodes[id].entry.name()),
+            Some(parent) => format!("{}/{}", self.path(parent), self.nodes[id].entry.name()),
+        }
+    }
+
+    /// `tree` style listing with sizes, directories deeper than `depth` are not expanded.
+    fn tree(&self, sizes: &[usize], sort: SortBy, depth: Option<usize>) -> String {
+        let mut out = format!("[{}]  /\n", sizes[ROOT]);
+        self.tree_children(ROOT, sizes, sort, depth, "", &mut out);
+        out
+    }
+
+    fn tree_children(
+        &self,
+        id: NodeId,
+        sizes: &[usize],
+        sort: SortBy,
+        depth: Option<usize>,
+        prefix: &str,
+        out: &mut String,
+    ) {
+        if depth == Some(0) {
+            return;
+        }
+        let children = self.sorted_children(id, sizes, sort);
+        for (i, child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let branch = if last { "└── " } else { "├── " };
+            let name = self.nodes[*child].entry.name();
+            out.push_str(&format!("{prefix}{branch}[{}]  {name}\n", sizes[*child]));
+            let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+            self.tree_children(*child, sizes, sort, depth.map(|d| d - 1), &prefix, out);
+        }
+    }
+
+    /// `du -h` style totals, children before their parent, limited to `depth` levels below `/`.
+    fn du(&self, sizes: &[usize], depth: Option<usize>) -> String {
+        let mut out = String::new();
+        self.du_directory(ROOT, sizes, depth, &mut out);
+        out
+    }
+
+    fn du_directory(&self, id: NodeId, sizes: &[usize], depth: Option<usize>, out: &mut String) {
+        for child in self.nodes[id].children.values() {
+            if self.is_directory(*child) {
+                self.du_directory(*child, sizes, depth, out);
+            }
+        }
+        if depth.is_none_or(|d| self.level(id) <= d) {
+            out.push_str(&format!("{}\t{}\n", human_size(sizes[id]), self.path(id)));
+        }
+    }
+
+    /// Number of directories between `id` and `/`.
+    fn level(&self, id: NodeId) -> usize {
+        std::iter::successors(self.nodes[id].parent, |p| self.nodes[*p].parent).count()
+    }
+
+    /// Recreates the tree under `root`, files are sparse so they take no real space.
+    fn materialize(&self, root: &Path) -> io::Result<()> {
+        for id in 0..self.nodes.len() {
+            let name = self.nodes[id].entry.name();
+            if id != ROOT && (name.contains('/') || name == "." || name == "..") {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{name} can not be created on disk"),
+                ));
+            }
+            let path = root.join(self.path(id).trim_start_matches('/'));
+            match &self.nodes[id].entry {
+                Entry::Directory(_) => std::fs::create_dir_all(path)?,
+                Entry::File(file) => std::fs::File::create(path)?.set_len(file.size as u64)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Materializes the tree under `root` and compares the sizes found by walking the disk
+    /// with `sizes`, listing every directory where the two disagree.
+    fn cross_check(&self, sizes: &[usize], root: &Path) -> io::Result<Vec<Discrepancy>> {
+        self.materialize(root)?;
+        let mut on_disk = BTreeMap::new();
+        disk_sizes(root, "/", &mut on_disk)?;
+        let mut discrepancies = Vec::new();
+        for dir in self.directories() {
+            let path = self.path(dir);
+            let disk = on_disk.remove(&path);
+            if disk != Some(sizes[dir]) {
+                discrepancies.push(Discrepancy {
+                    path,
+                    transcript: Some(sizes[dir]),
+                    disk,
+                });
+            }
+        }
+        discrepancies.extend(on_disk.into_iter().map(|(path, disk)| Discrepancy {
+            path,
+            transcript: None,
+            disk: Some(disk),
+        }));
+        Ok(discrepancies)
+    }
+
+    fn small_directories_total(&self, sizes: &[usize], threshold: usize) -> usize {
+        self.directories()
+            .map(|dir| sizes[dir])
+            .filter(|s| *s <= threshold)
+            .sum()
+    }
+
+    /// Size of the smallest directory whose deletion leaves `required` free, `Some(0)` when
+    /// there already is enough space.
+    fn smallest_to_delete(&self, sizes: &[usize], disk: Disk) -> Option<usize> {
+        let free = disk.capacity.saturating_sub(sizes[ROOT]);
+        let needed = disk.required.saturating_sub(free);
+        if needed == 0 {
+            return Some(0);
+        }
+        self.directories()
+            .map(|dir| sizes[dir])
+            .filter(|s| *s >= needed)
+            .min()
+    }
+}
+
+impl TryFrom<Vec<Command>> for FileSystem {
+    type Error = String;
+
+    fn try_from(commands: Vec<Command>) -> Result<Self, Self::Error> {
+        let mut fs = FileSystem::new();
+        let mut cwd = ROOT;
+        for command in commands {
+            match command {
+                Command::List(ls) => {
+                    fs.statistics.ls_count += 1;
+                    for entry in ls.0 {
+                        fs.insert(cwd, entry)?;
+                    }
+                }
+                Command::ChangeDir(cd) => {
+                    fs.statistics.cd_count += 1;
+                    cwd = fs.change_dir(cwd, cd)?;
+                }
+            }
+        }
+        Ok(fs)
+    }
+}
+
+/// Walks `dir` with `std::fs`, recording the total file length of every directory by the
+/// transcript style `path` it has below the walk's root.
+fn disk_sizes(dir: &Path, path: &str, sizes: &mut BTreeMap<String, usize>) -> io::Result<usize> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let child = format!("{}/{name}", path.trim_end_matches('/'));
+            total += disk_sizes(&entry.path(), &child, sizes)?;
+        } else {
+            total += metadata.len() as usize;
+        }
+    }
+    sizes.insert(path.to_owned(), total);
+    Ok(total)
+}
+
+#[derive(Debug, PartialEq)]
+struct Discrepancy {
+    path: String,
+    transcript: Option<usize>,
+    disk: Option<usize>,
+}
+impl Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |size: Option<usize>| size.map_or("missing".to_owned(), |s| s.to_string());
+        write!(
+            f,
+            "{}: transcript {}, disk {}",
+            self.path,
+            show(self.transcript),
+            show(self.disk)
+        )
+    }
+}
+
+/// Fresh directory under the system temp dir, removed with everything in it when dropped.
+struct TempDir(PathBuf);
+impl TempDir {
+    fn new(label: &str) -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("seven-{label}-{}", std::process::id()));
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+        std::fs::create_dir_all(&path)?;
+        Ok(Self(path))
+    }
+}
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Name,
+    Path,
+    Size,
+    Depth,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Glob,
+}
+
+/// Numeric expression, `root` is the total size of `/`.
+#[derive(Debug, PartialEq)]
+enum Value {
+    Number(usize),
+    Root,
+    Add(Box<Value>, Box<Value>),
+    Subtract(Box<Value>, Box<Value>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Operand {
+    Value(Value),
+    Text(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Condition {
+    Compare(Field, Operator, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Source {
+    Dirs,
+    Files,
+    All,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+    Sort(Field, bool),
+    Limit(usize),
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+/// `<source> [where <condition>] [| <stage>]...`, for example
+/// `files where name ~ "*.txt" | sort size desc | limit 5`.
+#[derive(Debug, PartialEq)]
+struct Query {
+    source: Source,
+    condition: Option<Condition>,
+    stages: Vec<Stage>,
+}
+
+peg::parser! {
+    grammar query_parser() for str {
+        rule _ = [' ' | '\t']*
+
+        rule number() -> usize
+            = n:$(['0'..='9']+) {? n.parse().or(Err("number")) }
+
+        rule text() -> String
+            = "\"" s:$([^ '"']*) "\"" { s.to_owned() }
+
+        rule field() -> Field
+            = "name" { Field::Name }
+            / "path" { Field::Path }
+            / "size" { Field::Size }
+            / "depth" { Field::Depth }
+
+        rule operator() -> Operator
+            = "<=" { Operator::LessEqual }
+            / ">=" { Operator::GreaterEqual }
+            / "!=" { Operator::NotEqual }
+            / "<" { Operator::Less }
+            / ">" { Operator::Greater }
+            / "=" { Operator::Equal }
+            / "~" { Operator::Glob }
+
+        rule value() -> Value = precedence! {
+            x:(@) _ "+" _ y:@ { Value::Add(Box::new(x), Box::new(y)) }
+            x:(@) _ "-" _ y:@ { Value::Subtract(Box::new(x), Box::new(y)) }
+            --
+            n:number() { Value::Number(n) }
+            "root" { Value::Root }
+            "(" _ v:value() _ ")" { v }
+        }
+
+        rule operand() -> Operand
+            = t:text() { Operand::Text(t) }
+            / v:value() { Operand::Value(v) }
+
+        rule condition() -> Condition = precedence! {
+            x:(@) _ "or" _ y:@ { Condition::Or(Box::new(x), Box::new(y)) }
+            --
+            x:(@) _ "and" _ y:@ { Condition::And(Box::new(x), Box::new(y)) }
+            --
+            "not" _ x:@ { Condition::Not(Box::new(x)) }
+            --
+            f:field() _ o:operator() _ v:operand() { Condition::Compare(f, o, v) }
+            "(" _ c:condition() _ ")" { c }
+        }
+
+        rule source() -> Source
+            = "dirs" { Source::Dirs }
+            / "files" { Source::Files }
+            / "all" { Source::All }
+
+        rule order() -> bool
+            = "desc" { true }
+            / "asc" { false }
+
+        rule stage() -> Stage
+            = "sort" _ f:field() d:(_ d:order() { d })? { Stage::Sort(f, d.unwrap_or(false)) }
+            / "limit" _ n:number() { Stage::Limit(n) }
+            / "sum" { Stage::Sum }
+            / "count" { Stage::Count }
+            / "min" { Stage::Min }
+            / "max" { Stage::Max }
+
+        pub rule query() -> Query
+            = _ source:source()
+              condition:(_ "where" _ c:condition() { c })?
+              stages:(_ "|" _ s:stage() { s })* _
+            { Query { source, condition, stages } }
+    }
+}
+
+impl TryFrom<&str> for Query {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        query_parser::query(value).map_err(|e| format!("{value} is not a valid query: {e}"))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Output {
+    Rows(Vec<NodeId>),
+    Number(Option<usize>),
+}
+
+/// Matches `*` against any run of characters and `?` against any single one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    (p, t) = (star + 1, matched + 1);
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[test]
+fn globs() {
+    assert!(glob_match("*.txt", "b.txt"));
+    assert!(!glob_match("*.txt", "b.txt.gz"));
+    assert!(glob_match("d.*", "d.log"));
+    assert!(glob_match("?", "k"));
+    assert!(glob_match("*a*b*", "xxaxxbxx"));
+    assert!(!glob_match("a?", "a"));
+    assert!(glob_match("*", ""));
+}
+
+impl FileSystem {
+    fn evaluate(&self, sizes: &[usize], value: &Value) -> usize {
+        match value {
+            Value::Number(n) => *n,
+            Value::Root => sizes[ROOT],
+            Value::Add(x, y) => self.evaluate(sizes, x) + self.evaluate(sizes, y),
+            Value::Subtract(x, y) => self
+                .evaluate(sizes, x)
+                .saturating_sub(self.evaluate(sizes, y)),
+        }
+    }
+
+    fn matches(&self, sizes: &[usize], id: NodeId, condition: &Condition) -> Result<bool, String> {
+        Ok(match condition {
+            Condition::And(x, y) => self.matches(sizes, id, x)? && self.matches(sizes, id, y)?,
+            Condition::Or(x, y) => self.matches(sizes, id, x)? || self.matches(sizes, id, y)?,
+            Condition::Not(x) => !self.matches(sizes, id, x)?,
+            Condition::Compare(field, operator, operand) => {
+                let ordering = match (field, operand) {
+                    (Field::Size | Field::Depth, Operand::Value(value)) => {
+                        if *operator == Operator::Glob {
+                            return Err(format!("{field:?} can not be matched with ~"));
                         }
-                        Entry::Directory(dir) => {
-                            let dir_size = *sizes.get(&dir.name).unwrap_or(&0);
-                            for back in stack.iter() {
-                                sizes
-                                    .entry(back.to_string())
-                                    .and_modify(|v| *v += dir_size)
-                                    .or_insert(dir_size);
-                            }
+                        let actual = match field {
+                            Field::Size => sizes[id],
+                            _ => self.level(id),
+                        };
+                        actual.cmp(&self.evaluate(sizes, value))
+                    }
+                    (Field::Name | Field::Path, Operand::Text(text)) => {
+                        let actual = match field {
+                            Field::Name => self.nodes[id].entry.name().to_owned(),
+                            _ => self.path(id),
+                        };
+                        if *operator == Operator::Glob {
+                            return Ok(glob_match(text, &actual));
                         }
+                        actual.as_str().cmp(text.as_str())
                     }
+                    _ => return Err(format!("{field:?} can not be compared with {operand:?}")),
+                };
+                match operator {
+                    Operator::Less => ordering.is_lt(),
+                    Operator::LessEqual => ordering.is_le(),
+                    Operator::Greater => ordering.is_gt(),
+                    Operator::GreaterEqual => ordering.is_ge(),
+                    Operator::Equal => ordering.is_eq(),
+                    Operator::NotEqual => ordering.is_ne(),
+                    Operator::Glob => unreachable!(),
                 }
             }
-            Command::ChangeDir(cd) => {
-                cd_count += 1;
-                match cd {
-                    ChangeDir::Up => {
-                        stack.pop().unwrap();
+        })
+    }
+
+    fn query(&self, sizes: &[usize], query: &Query) -> Result<Output, String> {
+        let mut rows = Vec::new();
+        for id in 0..self.nodes.len() {
+            let included = match query.source {
+                Source::Dirs => self.is_directory(id),
+                Source::Files => !self.is_directory(id),
+                Source::All => true,
+            };
+            if included
+                && query
+                    .condition
+                    .as_ref()
+                    .map_or(Ok(true), |c| self.matches(sizes, id, c))?
+            {
+                rows.push(id);
+            }
+        }
+        let mut stages = query.stages.iter();
+        while let Some(stage) = stages.next() {
+            let number = match stage {
+                Stage::Sort(field, descending) => {
+                    match field {
+                        Field::Name => rows.sort_by_key(|id| self.nodes[*id].entry.name()),
+                        Field::Path => rows.sort_by_cached_key(|id| self.path(*id)),
+                        Field::Size => rows.sort_by_key(|id| sizes[*id]),
+                        Field::Depth => rows.sort_by_cached_key(|id| self.level(*id)),
                     }
-                    ChangeDir::Down(dir) => {
-                        let name = stack.join("/") + "/" + &dir;
-                        stack.push(name);
+                    if *descending {
+                        rows.reverse();
                     }
+                    continue;
                 }
+                Stage::Limit(limit) => {
+                    rows.truncate(*limit);
+                    continue;
+                }
+                Stage::Sum => Some(rows.iter().map(|id| sizes[*id]).sum()),
+                Stage::Count => Some(rows.len()),
+                Stage::Min => rows.iter().map(|id| sizes[*id]).min(),
+                Stage::Max => rows.iter().map(|id| sizes[*id]).max(),
+            };
+            if let Some(stage) = stages.next() {
+                return Err(format!("{stage:?} follows an aggregate"));
             }
+            return Ok(Output::Number(number));
+        }
+        Ok(Output::Rows(rows))
+    }
+
+    fn render(&self, sizes: &[usize], output: &Output) -> String {
+        match output {
+            Output::Number(Some(number)) => format!("{number}\n"),
+            Output::Number(None) => "none\n".to_owned(),
+            Output::Rows(rows) => rows
+                .iter()
+                .map(|id| format!("{}\t{}\n", sizes[*id], self.path(*id)))
+                .collect(),
         }
     }
-    dbg!(cd_count, ls_count);
-    sizes
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Traversal {
+    /// `cd` into each directory by name and back out with `cd ..`.
+    DepthFirst,
+    /// Level by level, changing directory with absolute paths.
+    BreadthFirst,
+}
+impl TryFrom<&str> for Traversal {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "dfs" => Ok(Self::DepthFirst),
+            "bfs" => Ok(Self::BreadthFirst),
+            e => Err(format!("{e} is not a valid traversal order")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TranscriptOptions {
+    order: Traversal,
+    /// List every directory twice.
+    repeat: bool,
+}
+
+impl FileSystem {
+    /// Tree of the directory at `root` on disk, with file sizes taken from their metadata.
+    /// Names that would read back as a different transcript are rejected.
+    fn from_disk(root: &Path) -> io::Result<FileSystem> {
+        let mut fs = FileSystem::new();
+        let mut pending = vec![(root.to_path_buf(), ROOT)];
+        while let Some((dir, id)) = pending.pop() {
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.contains('\n') || name.contains("$ ") {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{} can not be written to a transcript",
+                            entry.path().display()
+                        ),
+                    ));
+                }
+                let metadata = entry.metadata()?;
+                let child = if metadata.is_dir() {
+                    Entry::Directory(Directory { name })
+                } else {
+                    Entry::File(File {
+                        name,
+                        size: metadata.len() as usize,
+                    })
+                };
+                let child = fs
+                    .insert(id, child)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                if metadata.is_dir() {
+                    pending.push((entry.path(), child));
+                }
+            }
+        }
+        Ok(fs)
+    }
+
+    /// Pseudo-random tree of `nodes` entries below `/`, the same `seed` gives the same tree.
+    fn random(seed: u64, nodes: usize) -> FileSystem {
+        let mut random = XorShift::new(seed);
+        let mut next = move |bound| random.below(bound);
+        let mut fs = FileSystem::new();
+        let mut directories = vec![ROOT];
+        while fs.nodes.len() <= nodes {
+            let parent = directories[next(directories.len())];
+            let mut name: String = (0..1 + next(8))
+                .map(|_| (b'a' + next(26) as u8) as char)
+                .collect();
+            if next(3) == 0 {
+                let entry = Entry::Directory(Directory { name });
+                if fs.nodes[parent].children.contains_key(entry.name()) {
+                    continue;
+                }
+                directories.push(fs.insert(parent, entry).unwrap());
+            } else {
+                if next(2) == 0 {
+                    name.push_str([".txt", ".dat", ".log"][next(3)]);
+                }
+                let entry = Entry::File(File {
+                    name,
+                    size: 1 + next(300000),
+                });
+                if fs.nodes[parent].children.contains_key(entry.name()) {
+                    continue;
+                }
+                fs.insert(parent, entry).unwrap();
+            }
+        }
+        fs
+    }
+
+    fn list_directory(&self, id: NodeId, options: TranscriptOptions, out: &mut String) {
+        for _ in 0..if options.repeat { 2 } else { 1 } {
+            out.push_str("$ ls\n");
+            for child in self.nodes[id].children.values() {
+                match &self.nodes[*child].entry {
+                    Entry::File(file) => out.push_str(&format!("{} {}\n", file.size, file.name)),
+                    Entry::Directory(dir) => out.push_str(&format!("dir {}\n", dir.name)),
+                }
+            }
+        }
+    }
+
+    /// Shell session that `Command::try_from` reads back into this tree.
+    fn transcript(&self, options: TranscriptOptions) -> String {
+        let mut out = "$ cd /\n".to_owned();
+        match options.order {
+            Traversal::DepthFirst => self.transcript_depth_first(ROOT, options, &mut out),
+            Traversal::BreadthFirst => {
+                let mut queue = VecDeque::from([ROOT]);
+                while let Some(id) = queue.pop_front() {
+                    if id != ROOT {
+                        out.push_str(&format!("$ cd {}\n", self.path(id)));
+                    }
+                    self.list_directory(id, options, &mut out);
+                    queue.extend(
+                        self.nodes[id]
+                            .children
+                            .values()
+                            .filter(|c| self.is_directory(**c)),
+                    );
+                }
+            }
+        }
+        out
+    }
+
+    fn transcript_depth_first(&self, id: NodeId, options: TranscriptOptions, out: &mut String) {
+        self.list_directory(id, options, out);
+        for child in self.nodes[id].children.values() {
+            if self.is_directory(*child) {
+                out.push_str(&format!("$ cd {}\n", self.nodes[*child].entry.name()));
+                self.transcript_depth_first(*child, options, out);
+                out.push_str("$ cd ..\n");
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SortBy {
+    Name,
+    Size,
+}
+impl TryFrom<&str> for SortBy {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "name" => Ok(Self::Name),
+            "size" => Ok(Self::Size),
+            e => Err(format!("{e} is not a valid sort order")),
+        }
+    }
+}
+
+/// Size in 1024 based units like `du -h`, rounded up, with one decimal below 10.
+fn human_size(size: usize) -> String {
+    let units = ["", "K", "M", "G", "T", "P"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    loop {
+        // Round first, so a value rounding up to the next unit is printed in that unit.
+        let rounded = match value {
+            v if v < 10.0 => (v * 10.0).ceil() / 10.0,
+            v => v.ceil(),
+        };
+        if rounded < 1024.0 || unit == units.len() - 1 {
+            return match units[unit] {
+                "" => size.to_string(),
+                u if rounded < 10.0 => format!("{rounded:.1}{u}"),
+                u => format!("{rounded}{u}"),
+            };
+        }
+        value /= 1024.0;
+        unit += 1;
+    }
+}
+
+#[test]
+fn human_sizes() {
+    assert_eq!(human_size(584), "584");
+    assert_eq!(human_size(1024), "1.0K");
+    assert_eq!(human_size(94853), "93K");
+    assert_eq!(human_size(8033020), "7.7M");
+    assert_eq!(human_size(48381165), "47M");
+    assert_eq!(human_size(1023), "1023");
+    assert_eq!(human_size(10230), "10K");
+    assert_eq!(human_size(1048575), "1.0M");
+}
+
+#[cfg(test)]
+fn parse(transcript: &str) -> FileSystem {
+    let commands = transcript
+        .split("$ ")
+        .filter(|l| !l.is_empty())
+        .map(Command::try_from)
+        .collect::<Result<Vec<_>, String>>()
+        .unwrap();
+    FileSystem::try_from(commands).unwrap()
+}
+
+#[cfg(test)]
+const SAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k
+";
+
+#[test]
+fn sample_sizes() {
+    let fs = parse(SAMPLE);
+    let sizes = fs.sizes();
+    assert_eq!(sizes[ROOT], 48381165);
+    let disk = Disk::default();
+    assert_eq!(fs.small_directories_total(&sizes, disk.threshold), 95437);
+    assert_eq!(fs.smallest_to_delete(&sizes, disk), Some(24933642));
+    assert_eq!(fs.statistics.cd_count, 6);
+    assert_eq!(fs.statistics.ls_count, 4);
+}
+
+#[test]
+fn disk_geometry() {
+    let fs = parse(SAMPLE);
+    let sizes = fs.sizes();
+    let disk = Disk {
+        capacity: 50000000,
+        required: 1700000,
+        threshold: 600,
+    };
+    assert_eq!(fs.small_directories_total(&sizes, disk.threshold), 584);
+    assert_eq!(fs.smallest_to_delete(&sizes, disk), Some(94853));
+    let roomy = Disk {
+        capacity: 100000000,
+        ..disk
+    };
+    assert_eq!(fs.smallest_to_delete(&sizes, roomy), Some(0));
+    let tiny = Disk {
+        capacity: 1000,
+        required: 50000000,
+        ..disk
+    };
+    assert_eq!(fs.smallest_to_delete(&sizes, tiny), None);
+}
+
+#[test]
+fn repeated_names_and_listings() {
+    let fs = parse(
+        "$ cd /\n$ ls\ndir a\ndir b\n$ cd a\n$ ls\ndir a\n10 x\n$ ls\n10 x\n\
+         $ cd /b\n$ ls\n5 x\n$ cd /a/a\n$ ls\n1 y\n$ cd /\n$ ls\ndir a\n",
+    );
+    let sizes = fs.sizes();
+    assert_eq!(sizes[ROOT], 16);
+    let a = fs.nodes[ROOT].children["a"];
+    let nested = fs.nodes[a].children["a"];
+    assert_eq!(sizes[a], 11);
+    assert_eq!(sizes[nested], 1);
+    assert_eq!(sizes[fs.nodes[ROOT].children["b"]], 5);
+    assert_eq!(fs.directories().count(), 4);
+}
+
+#[test]
+fn render_tree() {
+    let fs = parse(SAMPLE);
+    let sizes = fs.sizes();
+    assert_eq!(
+        fs.tree(&sizes, SortBy::Name, None),
+        "[48381165]  /
+├── [94853]  a
+│   ├── [584]  e
+│   │   └── [584]  i
+│   ├── [29116]  f
+│   ├── [2557]  g
+│   └── [62596]  h.lst
+├── [14848514]  b.txt
+├── [8504156]  c.dat
+└── [24933642]  d
+    ├── [5626152]  d.ext
+    ├── [8033020]  d.log
+    ├── [4060174]  j
+    └── [7214296]  k
+"
+    );
+    assert_eq!(
+        fs.tree(&sizes, SortBy::Size, Some(1)),
+        "[48381165]  /
+├── [24933642]  d
+├── [14848514]  b.txt
+├── [8504156]  c.dat
+└── [94853]  a
+"
+    );
+}
+
+#[test]
+fn render_du() {
+    let fs = parse(SAMPLE);
+    let sizes = fs.sizes();
+    assert_eq!(fs.du(&sizes, None), "584\t/a/e\n93K\t/a\n24M\t/d\n47M\t/\n");
+    assert_eq!(fs.du(&sizes, Some(1)), "93K\t/a\n24M\t/d\n47M\t/\n");
+    assert_eq!(fs.du(&sizes, Some(0)), "47M\t/\n");
+}
+
+#[test]
+fn cross_check_on_disk() {
+    let fs = parse(SAMPLE);
+    let sizes = fs.sizes();
+    let dir = TempDir::new("cross-check").unwrap();
+    assert_eq!(fs.cross_check(&sizes, &dir.0).unwrap(), vec![]);
+    let mut wrong = sizes.clone();
+    let a = fs.nodes[ROOT].children["a"];
+    wrong[a] += 1;
+    std::fs::create_dir(dir.0.join("stray")).unwrap();
+    assert_eq!(
+        fs.cross_check(&wrong, &dir.0).unwrap(),
+        vec![
+            Discrepancy {
+                path: "/a".to_owned(),
+                transcript: Some(94854),
+                disk: Some(94853),
+            },
+            Discrepancy {
+                path: "/stray".to_owned(),
+                transcript: None,
+                disk: Some(0),
+            },
+        ]
+    );
+}
+
+#[test]
+fn queries() {
+    let fs = parse(SAMPLE);
+    let sizes = fs.sizes();
+    let run = |query: &str| fs.query(&sizes, &Query::try_from(query).unwrap());
+    assert_eq!(
+        run("dirs where size <= 100000 | sum"),
+        Ok(Output::Number(Some(95437)))
+    );
+    assert_eq!(
+        run("dirs where size >= 30000000 - (70000000 - root) | min"),
+        Ok(Output::Number(Some(24933642)))
+    );
+    let largest = run("files where name ~ \"*.*\" | sort size desc | limit 2").unwrap();
+    assert_eq!(
+        fs.render(&sizes, &largest),
+        "14848514\t/b.txt\n8504156\t/c.dat\n"
+    );
+    assert_eq!(
+        run("all where depth = 2 and not (name = \"e\" or size > 60000) | count"),
+        Ok(Output::Number(Some(2)))
+    );
+    assert_eq!(
+        run("dirs where path ~ \"/a*\" | sort path"),
+        Ok(Output::Rows(vec![
+            fs.nodes[ROOT].children["a"],
+            fs.nodes[fs.nodes[ROOT].children["a"]].children["e"]
+        ]))
+    );
+    assert_eq!(
+        run("files where size > 1 | max | count").map(|_| ()),
+        Err("Count follows an aggregate".into())
+    );
+    assert!(run("dirs where name > 5").is_err());
+    assert!(run("dirs where size ~ \"*\"").is_err());
+    assert_eq!(
+        run("dirs where size > root | min"),
+        Ok(Output::Number(None))
+    );
+    assert!(Query::try_from("dirs where").is_err());
+    assert!(Query::try_from("folders").is_err());
+}
+
+#[cfg(test)]
+impl FileSystem {
+    /// Every entry as its path and size, `None` for directories.
+    fn listing(&self) -> Vec<(String, Option<usize>)> {
+        let mut listing: Vec<_> = (0..self.nodes.len())
+            .map(|id| match &self.nodes[id].entry {
+                Entry::File(file) => (self.path(id), Some(file.size)),
+                Entry::Directory(_) => (self.path(id), None),
+            })
+            .collect();
+        listing.sort();
+        listing
+    }
+}
+
+#[test]
+fn transcript_round_trip() {
+    let sample = parse(SAMPLE);
+    let depth_first = TranscriptOptions {
+        order: Traversal::DepthFirst,
+        repeat: false,
+    };
+    assert_eq!(
+        parse(&sample.transcript(depth_first)).listing(),
+        sample.listing()
+    );
+    for seed in [1, 7, 1234] {
+        let fs = FileSystem::random(seed, 300);
+        assert_eq!(fs.nodes.len(), 301);
+        for order in [Traversal::DepthFirst, Traversal::BreadthFirst] {
+            for repeat in [false, true] {
+                let transcript = fs.transcript(TranscriptOptions { order, repeat });
+                let parsed = parse(&transcript);
+                assert_eq!(parsed.listing(), fs.listing());
+                assert_eq!(parsed.sizes()[ROOT], fs.sizes()[ROOT]);
+            }
+        }
+    }
+    assert_eq!(
+        FileSystem::random(7, 50).listing(),
+        FileSystem::random(7, 50).listing()
+    );
+}
+
+#[test]
+fn transcript_from_disk() {
+    let fs = parse(SAMPLE);
+    let dir = TempDir::new("from-disk").unwrap();
+    fs.materialize(&dir.0).unwrap();
+    let from_disk = FileSystem::from_disk(&dir.0).unwrap();
+    assert_eq!(from_disk.listing(), fs.listing());
+    let options = TranscriptOptions {
+        order: Traversal::BreadthFirst,
+        repeat: true,
+    };
+    assert_eq!(
+        parse(&from_disk.transcript(options)).listing(),
+        fs.listing()
+    );
+    for name in ["two\nlines", "cash$ cd x"] {
+        let dir = TempDir::new("unsafe-name").unwrap();
+        std::fs::write(dir.0.join(name), "").unwrap();
+        let error = FileSystem::from_disk(&dir.0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains(name));
+    }
 }
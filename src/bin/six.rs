@@ -1,33 +1,325 @@
-#![feature(iter_next_chunk)]
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io::{self, Read},
+};
+
 use advent_of_code::load_file;
 
-const MARKER: usize = 14;
+const PACKET: usize = 4;
+const MESSAGE: usize = 14;
+
+const CHUNK: usize = 64 * 1024;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [mode, length, source @ ..] = &args[..] {
+        if mode == "stream" {
+            let length = length.parse().unwrap();
+            let reader: Box<dyn Read> = match source {
+                [path] => Box::new(std::fs::File::open(path).unwrap()),
+                _ => Box::new(io::stdin().lock()),
+            };
+            for position in Markers::new(reader, length, CHUNK).unwrap() {
+                println!("{}", position.unwrap());
+            }
+            return;
+        }
+    }
     let data = load_file!("six");
-    let mut chars = data.chars();
-    let mut buffer: [char; MARKER] = chars.next_chunk().unwrap();
-    for (i, ch) in chars.enumerate() {
-        buffer.rotate_left(1);
-        buffer[MARKER - 1] = ch;
-        if check_unique(&buffer) {
-            println!(
-                "Ding ding, the answer is {} at {}",
-                String::from_iter(buffer.iter()),
-                i + MARKER + 1
-            );
-            break;
+    if let [mode] = &args[..] {
+        if mode == "decode" {
+            let decoded = decode(data.as_bytes()).unwrap();
+            println!("start-of-packet at {}", decoded.packet);
+            for frame in decoded.frames {
+                println!("{frame}");
+            }
+            return;
         }
     }
+    let mut lengths = std::env::args()
+        .skip(1)
+        .map(|arg| arg.parse().unwrap())
+        .collect::<Vec<usize>>();
+    if lengths.is_empty() {
+        lengths = vec![PACKET, MESSAGE];
+    }
+    let positions = first_markers(data.as_bytes(), &lengths).unwrap();
+    for (length, position) in lengths.iter().zip(positions) {
+        match position {
+            Some(position) => println!("Ding ding, the {length} marker answer is {position}"),
+            None => println!("No marker of length {length}"),
+        }
+    }
+}
+
+/// Sliding window over the last `length` bytes, counting how often each byte occurs in it.
+struct MarkerWindow {
+    length: usize,
+    window: VecDeque<u8>,
+    counts: [usize; 256],
+    duplicates: usize,
 }
 
-fn check_unique(chars: &[char; MARKER]) -> bool {
-    for x in 0..MARKER {
-        for y in 0..MARKER {
-            if x != y && chars[x] == chars[y] {
-                return false;
+impl MarkerWindow {
+    fn new(length: usize) -> Result<Self, String> {
+        if length == 0 {
+            return Err("marker length must be at least 1".to_owned());
+        }
+        Ok(Self {
+            length,
+            window: VecDeque::with_capacity(length),
+            counts: [0; 256],
+            duplicates: 0,
+        })
+    }
+
+    fn clear(&mut self) {
+        self.window.clear();
+        self.counts = [0; 256];
+        self.duplicates = 0;
+    }
+
+    /// Adds the next byte, returns whether the window now holds a marker.
+    fn push(&mut self, byte: u8) -> bool {
+        if self.window.len() == self.length {
+            let old = self.window.pop_front().unwrap();
+            self.counts[old as usize] -= 1;
+            if self.counts[old as usize] == 1 {
+                self.duplicates -= 1;
             }
         }
+        self.window.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 2 {
+            self.duplicates += 1;
+        }
+        self.window.len() == self.length && self.duplicates == 0
+    }
+}
+
+/// Message payload between two start-of-message markers.
+#[derive(Debug, PartialEq)]
+struct Frame {
+    /// Bytes read before the payload starts, the end of its start-of-message marker.
+    offset: usize,
+    length: usize,
+    distinct: usize,
+    /// Most consecutive payload bytes without a start-of-packet marker completing.
+    longest_unmarked: usize,
+}
+
+impl Frame {
+    fn new(data: &[u8], offset: usize, length: usize) -> Self {
+        let payload = &data[offset..offset + length];
+        let mut seen = [false; 256];
+        payload.iter().for_each(|byte| seen[*byte as usize] = true);
+        let mut window = MarkerWindow::new(PACKET).unwrap();
+        let (mut run, mut longest_unmarked) = (0, 0);
+        for byte in payload {
+            if window.push(*byte) {
+                run = 0;
+            } else {
+                run += 1;
+                longest_unmarked = longest_unmarked.max(run);
+            }
+        }
+        Self {
+            offset,
+            length,
+            distinct: seen.iter().filter(|s| **s).count(),
+            longest_unmarked,
+        }
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frame at {} of {} bytes, {} distinct, {} without a marker",
+            self.offset, self.length, self.distinct, self.longest_unmarked
+        )
     }
-    true
+}
+
+#[derive(Debug, PartialEq)]
+struct Decoded {
+    packet: usize,
+    frames: Vec<Frame>,
+}
+
+/// Finds the start-of-packet marker, then splits the rest of the stream into frames. Each
+/// start-of-message marker opens a frame that runs until the next marker begins, the window
+/// starts over after every marker so markers never overlap.
+fn decode(data: &[u8]) -> Result<Decoded, String> {
+    let packet = first_markers(data, &[PACKET])?[0].ok_or("no start-of-packet marker")?;
+    let mut window = MarkerWindow::new(MESSAGE)?;
+    let mut frames = Vec::new();
+    let mut start = None;
+    for (i, byte) in data.iter().enumerate().skip(packet) {
+        if window.push(*byte) {
+            if let Some(start) = start {
+                frames.push(Frame::new(data, start, i + 1 - MESSAGE - start));
+            }
+            start = Some(i + 1);
+            window.clear();
+        }
+    }
+    if let Some(start) = start {
+        frames.push(Frame::new(data, start, data.len() - start));
+    }
+    Ok(Decoded { packet, frames })
+}
+
+/// Every position at which a marker of `length` completes, read from `reader` in chunks.
+struct Markers<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    filled: usize,
+    cursor: usize,
+    position: usize,
+    window: MarkerWindow,
+    done: bool,
+}
+
+impl<R: Read> Markers<R> {
+    fn new(reader: R, length: usize, chunk: usize) -> Result<Self, String> {
+        if chunk == 0 {
+            return Err("chunk size must be at least 1".to_owned());
+        }
+        Ok(Self {
+            reader,
+            buffer: vec![0; chunk],
+            filled: 0,
+            cursor: 0,
+            position: 0,
+            window: MarkerWindow::new(length)?,
+            done: false,
+        })
+    }
+}
+
+impl<R: Read> Iterator for Markers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.cursor == self.filled {
+                match self.reader.read(&mut self.buffer) {
+                    Ok(0) => self.done = true,
+                    Ok(read) => (self.filled, self.cursor) = (read, 0),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+                continue;
+            }
+            let byte = self.buffer[self.cursor];
+            self.cursor += 1;
+            self.position += 1;
+            if self.window.push(byte) {
+                return Some(Ok(self.position));
+            }
+        }
+        None
+    }
+}
+
+/// Number of bytes read when the first marker of each length completes, in one pass.
+fn first_markers(data: &[u8], lengths: &[usize]) -> Result<Vec<Option<usize>>, String> {
+    let mut windows = lengths
+        .iter()
+        .map(|length| MarkerWindow::new(*length))
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut positions = vec![None; lengths.len()];
+    for (i, byte) in data.iter().enumerate() {
+        for (window, position) in windows.iter_mut().zip(positions.iter_mut()) {
+            if window.push(*byte) && position.is_none() {
+                *position = Some(i + 1);
+            }
+        }
+        if positions.iter().all(Option::is_some) {
+            break;
+        }
+    }
+    Ok(positions)
+}
+
+#[test]
+fn sample_markers() {
+    let samples = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+        ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+        ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+        ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+    ];
+    for (data, packet, message) in samples {
+        assert_eq!(
+            first_markers(data.as_bytes(), &[PACKET, MESSAGE]),
+            Ok(vec![Some(packet), Some(message)])
+        );
+    }
+    assert_eq!(
+        first_markers(b"aab", &[1, 2, 3]),
+        Ok(vec![Some(1), Some(3), None])
+    );
+    assert!(first_markers(b"abc", &[0]).is_err());
+}
+
+#[test]
+fn streamed_markers() {
+    let data = b"aabcaddefghiijk";
+    let expected: Vec<usize> = (4..=data.len())
+        .filter(|end| {
+            let window = &data[end - 4..*end];
+            (0..4).all(|x| (0..4).all(|y| x == y || window[x] != window[y]))
+        })
+        .collect();
+    assert_eq!(expected, vec![6, 10, 11, 12]);
+    for chunk in [1, 3, 4, 7, CHUNK] {
+        let markers = Markers::new(&data[..], 4, chunk)
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(markers, expected);
+    }
+    assert!(Markers::new(&data[..], 4, 0).is_err());
+    assert_eq!(Markers::new(&b""[..], 4, 8).unwrap().count(), 0);
+}
+
+#[test]
+fn decode_frames() {
+    let stream = b"aaabcd\
+        abcdefghijklmn\
+        xxyyxx\
+        opqrstuvwxyzAB\
+        abab";
+    let decoded = decode(stream).unwrap();
+    assert_eq!(decoded.packet, 6);
+    assert_eq!(
+        decoded.frames,
+        vec![
+            Frame {
+                offset: 20,
+                length: 6,
+                distinct: 2,
+                longest_unmarked: 6,
+            },
+            Frame {
+                offset: 40,
+                length: 4,
+                distinct: 2,
+                longest_unmarked: 4,
+            },
+        ]
+    );
+    let decoded = decode(b"abcdabcdefghijklmnabcdefg").unwrap();
+    assert_eq!(decoded.frames[0].offset, 18);
+    assert_eq!(decoded.frames[0].longest_unmarked, 3);
+    assert_eq!(decode(b"abcdaaaa").unwrap().frames, vec![]);
+    assert!(decode(b"aaaa").is_err());
 }
@@ -1,26 +1,42 @@
-#![feature(iter_array_chunks)]
-use std::collections::BTreeSet;
+#![feature(test)]
+extern crate test;
+
+use std::ops::{BitAnd, BitOr};
 
 use advent_of_code::load_file;
+#[cfg(test)]
+use advent_of_code::random::XorShift;
 
 fn main() {
     let data = load_file!("three");
-    let list = data
+    let bags = data
         .split('\n')
-        .array_chunks::<3>()
-        .map(|s| (s[0], s[1], s[2]).try_into())
-        .collect::<Result<Vec<Group>, String>>()
+        .map(Bag::try_from)
+        .collect::<Result<Vec<Bag>, String>>()
+        .unwrap();
+    let shared_items = bags
+        .iter()
+        .map(|bag| bag.shared_item())
+        .collect::<Option<Vec<Item>>>()
         .unwrap();
+    let priorities: u32 = shared_items.iter().map(|i| i.value()).sum();
+    println!("Ding ding, the item answer is {priorities}");
+    let group_size = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().unwrap())
+        .unwrap_or(3);
+    let lines = data.split('\n').collect::<Vec<&str>>();
+    let list = Group::split(&lines, group_size).unwrap();
     let shared_badges = list
         .iter()
         .map(|group| group.shared_badge())
         .collect::<Option<Vec<Item>>>()
         .unwrap();
     let priorities: u32 = shared_badges.iter().map(|i| i.value()).sum();
-    println!("Ding ding, the answer is {priorities}");
+    println!("Ding ding, the badge answer is {priorities}");
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 struct Item(char);
 
 impl Item {
@@ -31,6 +47,14 @@ impl Item {
             _ => 0,
         }
     }
+
+    fn from_value(value: u32) -> Option<Self> {
+        match value {
+            1..=26 => char::from_u32(value + 96).map(Self),
+            27..=52 => char::from_u32(value + 64 - 26).map(Self),
+            _ => None,
+        }
+    }
 }
 
 impl TryFrom<char> for Item {
@@ -38,25 +62,100 @@ impl TryFrom<char> for Item {
 
     fn try_from(s: char) -> Result<Self, Self::Error> {
         match s {
-            c @ 'A'..='z' => Ok(Self(c)),
+            c @ ('a'..='z' | 'A'..='Z') => Ok(Self(c)),
             v => Err(format!("{v} is not valid Item")),
         }
     }
 }
 
+/// Set of items stored as a bitmask, bit `n` is set when the item with value `n` is present.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    fn insert(&mut self, item: Item) {
+        self.0 |= 1 << item.value();
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    fn iter(self) -> ItemSetIter {
+        ItemSetIter(self.0)
+    }
+}
+
+impl BitOr for ItemSet {
+    type Output = ItemSet;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+impl BitAnd for ItemSet {
+    type Output = ItemSet;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(rhs)
+    }
+}
+
+impl<'a> FromIterator<&'a Item> for ItemSet {
+    fn from_iter<T: IntoIterator<Item = &'a Item>>(iter: T) -> Self {
+        let mut set = Self::default();
+        for item in iter {
+            set.insert(*item);
+        }
+        set
+    }
+}
+
+struct ItemSetIter(u64);
+
+impl Iterator for ItemSetIter {
+    type Item = Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+        let value = self.0.trailing_zeros();
+        self.0 &= self.0 - 1;
+        Item::from_value(value)
+    }
+}
+
+#[test]
+fn item_set_ops() {
+    let a: ItemSet = [Item('a'), Item('Z'), Item('c')].iter().collect();
+    let b: ItemSet = [Item('c'), Item('Z'), Item('q')].iter().collect();
+    assert_eq!(a.iter().count(), 3);
+    assert_eq!((a | b).iter().count(), 4);
+    assert_eq!(
+        (a & b).iter().collect::<Vec<_>>(),
+        vec![Item('c'), Item('Z')]
+    );
+    assert_eq!(a & ItemSet::default(), ItemSet::default());
+}
+
 struct Bag(Vec<Item>);
 
 impl Bag {
     fn shared_item(&self) -> Option<Item> {
         let (first_pocket, second_pocket) = self.0.split_at(self.0.len() / 2);
-        let first_pocket = BTreeSet::from_iter(first_pocket.iter());
-        let second_pocket = BTreeSet::from_iter(second_pocket.iter());
-        let shared_item = first_pocket
-            .intersection(&second_pocket)
-            .next()
-            .cloned()
-            .cloned();
-        shared_item
+        let first_pocket = ItemSet::from_iter(first_pocket);
+        let second_pocket = ItemSet::from_iter(second_pocket);
+        first_pocket.intersection(second_pocket).iter().next()
+    }
+
+    fn items(&self) -> ItemSet {
+        self.0.iter().collect()
     }
 }
 
@@ -72,32 +171,132 @@ impl TryFrom<&str> for Bag {
     }
 }
 
-struct Group(Bag, Bag, Bag);
+#[test]
+fn item_alphabet() {
+    assert!(Item::try_from('a').is_ok());
+    assert!(Item::try_from('Z').is_ok());
+    for ch in ['[', '\\', '^', '_', '`', '1', ' '] {
+        assert!(Item::try_from(ch).is_err());
+    }
+}
 
-impl TryFrom<(&str, &str, &str)> for Group {
+struct Group(Vec<Bag>);
+
+impl TryFrom<&[&str]> for Group {
     type Error = String;
-    fn try_from(value: (&str, &str, &str)) -> Result<Self, Self::Error> {
+    fn try_from(value: &[&str]) -> Result<Self, Self::Error> {
         Ok(Group(
-            value.0.try_into()?,
-            value.1.try_into()?,
-            value.2.try_into()?,
+            value
+                .iter()
+                .map(|line| Bag::try_from(*line))
+                .collect::<Result<Vec<Bag>, String>>()?,
         ))
     }
 }
 
 impl Group {
+    fn split(lines: &[&str], size: usize) -> Result<Vec<Group>, String> {
+        if size == 0 {
+            return Err("group size must be at least 1".to_owned());
+        }
+        if !lines.len().is_multiple_of(size) {
+            return Err(format!(
+                "{} lines can not be split into groups of {size}",
+                lines.len()
+            ));
+        }
+        lines.chunks(size).map(Group::try_from).collect()
+    }
+
     fn shared_badge(&self) -> Option<Item> {
-        let one = BTreeSet::from_iter((self.0).0.iter());
-        let two = BTreeSet::from_iter((self.1).0.iter());
-        let three = BTreeSet::from_iter((self.2).0.iter());
-        let first_intersect: BTreeSet<&&Item> = one.intersection(&two).collect();
-        let second_intersect = two.intersection(&three).collect();
-        let shared_badge = first_intersect
-            .intersection(&second_intersect)
+        self.0
+            .iter()
+            .map(Bag::items)
+            .reduce(ItemSet::intersection)?
+            .iter()
             .next()
-            .cloned()
-            .cloned()
-            .cloned();
-        shared_badge
     }
 }
+
+#[cfg(test)]
+const SAMPLE: &str = "vJrwpWtwJgWrhcsFMMfFFhFp
+jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
+PmmdzqPrVvPwwTWBwg
+wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
+ttgJtRGJQctTZtZT
+CrZsJsPPZsGzwwsLwLmpwMDw";
+
+#[test]
+fn sample_priorities() {
+    let bags: Vec<Bag> = SAMPLE.split('\n').map(|l| l.try_into().unwrap()).collect();
+    let items: u32 = bags.iter().map(|b| b.shared_item().unwrap().value()).sum();
+    assert_eq!(items, 157);
+    let lines = SAMPLE.split('\n').collect::<Vec<&str>>();
+    let badges: u32 = Group::split(&lines, 3)
+        .unwrap()
+        .iter()
+        .map(|g| g.shared_badge().unwrap().value())
+        .sum();
+    assert_eq!(badges, 70);
+}
+
+#[test]
+fn group_sizes() {
+    let lines = SAMPLE.split('\n').collect::<Vec<&str>>();
+    assert_eq!(Group::split(&lines, 2).unwrap().len(), 3);
+    assert_eq!(Group::split(&lines, 6).unwrap().len(), 1);
+    assert!(Group::split(&lines, 4).is_err());
+    assert!(Group::split(&lines, 0).is_err());
+    let single = Group::split(&lines[..1], 1).unwrap();
+    assert_eq!(single[0].shared_badge(), Some(Item('c')));
+}
+
+/// Large pseudo-random input for the benchmarks, every bag holds exactly one shared item.
+#[cfg(test)]
+fn generate_bags(count: usize) -> Vec<Bag> {
+    let mut random = XorShift::new(0x2545_F491_4F6C_DD1D);
+    let mut next = move |bound| random.below(bound);
+    (0..count)
+        .map(|_| {
+            let shared = Item::from_value(next(52) as u32 + 1).unwrap();
+            let half = 20 + next(20);
+            // Lowercase on the left and uppercase on the right so only `shared` overlaps.
+            let mut first: Vec<Item> = (0..half)
+                .map(|_| Item::from_value(next(26) as u32 + 1).unwrap())
+                .collect();
+            let mut second: Vec<Item> = (0..half)
+                .map(|_| Item::from_value(next(26) as u32 + 27).unwrap())
+                .collect();
+            first[next(half)] = shared;
+            second[next(half)] = shared;
+            first.append(&mut second);
+            Bag(first)
+        })
+        .collect()
+}
+
+#[bench]
+fn bench_shared_item_btree(b: &mut test::Bencher) {
+    use std::collections::BTreeSet;
+    let bags = generate_bags(10_000);
+    b.iter(|| {
+        bags.iter()
+            .map(|bag| {
+                let (first, second) = bag.0.split_at(bag.0.len() / 2);
+                let first = BTreeSet::from_iter(first.iter());
+                let second = BTreeSet::from_iter(second.iter());
+                first.intersection(&second).next().unwrap().value()
+            })
+            .sum::<u32>()
+    });
+}
+
+#[bench]
+fn bench_shared_item_bitset(b: &mut test::Bencher) {
+    let bags = generate_bags(10_000);
+    b.iter(|| {
+        bags.iter()
+            .map(|bag| bag.shared_item().unwrap().value())
+            .sum::<u32>()
+    });
+}
@@ -0,0 +1,253 @@
+use std::fmt::Display;
+
+/// Inclusive range of integers, `start` is never greater than `end`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interval {
+    start: i64,
+    end: i64,
+}
+
+impl Interval {
+    pub fn new(start: i64, end: i64) -> Option<Self> {
+        (start <= end).then_some(Self { start, end })
+    }
+
+    pub fn point(at: i64) -> Self {
+        Self { start: at, end: at }
+    }
+
+    pub fn start(self) -> i64 {
+        self.start
+    }
+
+    pub fn end(self) -> i64 {
+        self.end
+    }
+
+    /// Number of integers covered, `None` only for the full `i64` range which has 2^64.
+    pub fn length(self) -> Option<u64> {
+        self.end.abs_diff(self.start).checked_add(1)
+    }
+
+    pub fn contains(self, other: Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn contains_point(self, at: i64) -> bool {
+        self.start <= at && at <= self.end
+    }
+
+    pub fn overlaps(self, other: Interval) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// Overlapping or directly adjacent, so the two can be merged into one interval.
+    pub fn touches(self, other: Interval) -> bool {
+        self.start <= other.end.saturating_add(1) && other.start <= self.end.saturating_add(1)
+    }
+
+    pub fn intersection(self, other: Interval) -> Option<Interval> {
+        Self::new(self.start.max(other.start), self.end.min(other.end))
+    }
+
+    /// Single interval covering both, only when they touch.
+    pub fn union(self, other: Interval) -> Option<Interval> {
+        self.touches(other).then(|| Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        })
+    }
+
+    /// Parts of `self` not covered by `other`, at most one on each side.
+    pub fn difference(self, other: Interval) -> impl Iterator<Item = Interval> {
+        if !self.overlaps(other) {
+            return [Some(self), None].into_iter().flatten();
+        }
+        let left = Self::new(self.start, other.start.saturating_sub(1))
+            .filter(|_| other.start > self.start);
+        let right =
+            Self::new(other.end.saturating_add(1), self.end).filter(|_| other.end < self.end);
+        [left, right].into_iter().flatten()
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl TryFrom<&str> for Interval {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or(format!("{value} is not valid Interval"))?;
+        Self::new(
+            start.parse().or(Err(format!("{start} is not i64")))?,
+            end.parse().or(Err(format!("{end} is not i64")))?,
+        )
+        .ok_or(format!("{value} ends before it starts"))
+    }
+}
+
+#[test]
+fn interval_algebra() {
+    let a = Interval::new(2, 6).unwrap();
+    let b = Interval::new(5, 9).unwrap();
+    assert_eq!(Interval::new(3, 2), None);
+    assert_eq!(a.length(), Some(5));
+    assert_eq!(Interval::point(4).length(), Some(1));
+    assert_eq!(Interval::new(i64::MIN, i64::MAX).unwrap().length(), None);
+    assert_eq!(
+        Interval::new(i64::MIN, i64::MAX - 1).unwrap().length(),
+        Some(u64::MAX)
+    );
+    assert_eq!(a.intersection(b), Interval::new(5, 6));
+    assert_eq!(a.intersection(Interval::point(7)), None);
+    assert_eq!(a.union(b), Interval::new(2, 9));
+    assert_eq!(a.union(Interval::point(7)), Interval::new(2, 7));
+    assert_eq!(a.union(Interval::point(8)), None);
+    assert!(a.contains(Interval::point(2)));
+    assert!(!a.contains(b));
+    let diff: Vec<_> = a.difference(Interval::new(3, 4).unwrap()).collect();
+    assert_eq!(diff, vec![Interval::point(2), Interval::new(5, 6).unwrap()]);
+    assert_eq!(
+        a.difference(b).collect::<Vec<_>>(),
+        vec![Interval::new(2, 4).unwrap()]
+    );
+    assert_eq!(a.difference(a).count(), 0);
+    assert_eq!(
+        a.difference(Interval::point(9)).collect::<Vec<_>>(),
+        vec![a]
+    );
+    assert_eq!(Interval::try_from("2-6"), Ok(a));
+    assert!(Interval::try_from("6-2").is_err());
+}
+
+/// Disjoint, non-adjacent intervals kept in ascending order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntervalSet(Vec<Interval>);
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, interval: Interval) {
+        let first = self
+            .0
+            .partition_point(|iv| iv.end.saturating_add(1) < interval.start);
+        let mut merged = interval;
+        let mut last = first;
+        while let Some(joined) = self.0.get(last).and_then(|iv| iv.union(merged)) {
+            merged = joined;
+            last += 1;
+        }
+        self.0.splice(first..last, [merged]);
+    }
+
+    pub fn remove(&mut self, interval: Interval) {
+        self.0 = self
+            .0
+            .iter()
+            .flat_map(|iv| iv.difference(interval))
+            .collect();
+    }
+
+    pub fn merge(&mut self, other: &IntervalSet) {
+        for interval in other.iter() {
+            self.insert(interval);
+        }
+    }
+
+    /// Total number of integers covered, `None` when that does not fit in a `u64`.
+    pub fn coverage(&self) -> Option<u64> {
+        self.0
+            .iter()
+            .try_fold(0u64, |total, iv| total.checked_add(iv.length()?))
+    }
+
+    pub fn contains_point(&self, at: i64) -> bool {
+        let index = self.0.partition_point(|iv| iv.end < at);
+        self.0.get(index).is_some_and(|iv| iv.contains_point(at))
+    }
+
+    /// Whether every integer of `interval` is in the set.
+    pub fn covers(&self, interval: Interval) -> bool {
+        let index = self.0.partition_point(|iv| iv.end < interval.start);
+        self.0.get(index).is_some_and(|iv| iv.contains(interval))
+    }
+
+    /// Parts of `interval` that are not in the set.
+    pub fn gaps(&self, interval: Interval) -> IntervalSet {
+        let mut gaps = IntervalSet::from_iter([interval]);
+        for iv in self.iter() {
+            gaps.remove(iv);
+        }
+        gaps
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Interval> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = Interval>>(iter: T) -> Self {
+        let mut set = Self::new();
+        for interval in iter {
+            set.insert(interval);
+        }
+        set
+    }
+}
+
+impl Display for IntervalSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, interval) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{interval}")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn interval_set() {
+    let iv = |s, e| Interval::new(s, e).unwrap();
+    let mut set = IntervalSet::from_iter([iv(10, 12), iv(1, 3), iv(6, 7)]);
+    assert_eq!(set.to_string(), "1-3,6-7,10-12");
+    set.insert(iv(4, 5));
+    assert_eq!(set.to_string(), "1-7,10-12");
+    set.insert(iv(0, 20));
+    assert_eq!(set.to_string(), "0-20");
+    set.remove(iv(5, 8));
+    assert_eq!(set.to_string(), "0-4,9-20");
+    set.remove(Interval::point(0));
+    assert_eq!(set.coverage(), Some(16));
+    assert!(set.contains_point(9));
+    assert!(!set.contains_point(7));
+    assert!(set.covers(iv(10, 15)));
+    assert!(!set.covers(iv(3, 10)));
+    assert_eq!(set.gaps(iv(0, 10)).to_string(), "0-0,5-8");
+    let mut other = IntervalSet::from_iter([iv(5, 8), iv(30, 31)]);
+    other.merge(&set);
+    assert_eq!(other.to_string(), "1-20,30-31");
+    assert_eq!(other.len(), 2);
+    let mut full = IntervalSet::from_iter([iv(i64::MIN, -1), iv(1, i64::MAX)]);
+    assert_eq!(full.coverage(), Some(u64::MAX));
+    full.insert(Interval::point(0));
+    assert_eq!(full.coverage(), None);
+}
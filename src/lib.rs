@@ -1,3 +1,6 @@
+pub mod interval;
+pub mod random;
+
 #[macro_export]
 macro_rules! load_file {
     ($file:literal) => {
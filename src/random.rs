@@ -0,0 +1,35 @@
+/// Small xorshift generator for reproducible test data, not for anything needing real
+/// randomness. The same seed always gives the same sequence.
+#[derive(Clone, Debug)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    /// Zero is a fixed point of xorshift, so it is replaced by one.
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Number in `0..bound`, `bound` must not be zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[test]
+fn xorshift_sequence() {
+    let mut a = XorShift::new(42);
+    let mut b = XorShift::new(42);
+    for _ in 0..100 {
+        assert_eq!(a.next_u64(), b.next_u64());
+        assert!(a.below(7) < 7);
+        b.below(7);
+    }
+    assert_ne!(XorShift::new(0).next_u64(), 0);
+}